@@ -0,0 +1,1039 @@
+//! Geometry for rendering OpenStreetMap ways and relations as SVG,
+//! and reading the ids of the ones to render.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    f64::consts::PI,
+    fmt,
+};
+
+use osmpbfreader::{Node, NodeId, OsmId, OsmObj, Relation, Tags, Way, WayId};
+
+/// Projected units per radian.
+pub const SCALE: f64 = 6371.0 * 100.0;
+
+/// Mean earth radius in meters, for geodesic measurements.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Why reading or rendering OSM data failed.
+#[derive(Debug)]
+pub enum RenderError {
+    /// A file couldn't be read or written.
+    Io(std::io::Error),
+    /// The `.osm.pbf` file isn't valid.
+    PbfParse(osmpbfreader::Error),
+    /// A line of an id file, counting from 1, isn't an id.
+    IdParse { line: usize, reason: String },
+    /// An object that was asked for by id isn't in the extract.
+    MissingReference { id: OsmId },
+    /// An object that was asked for by id has none of its nodes in the extract.
+    NothingToDraw { id: OsmId },
+    /// Nothing that was asked for is in the extract.
+    EmptySelection,
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::Io(e) => write!(f, "{e}"),
+            RenderError::PbfParse(e) => write!(f, "{e}"),
+            RenderError::IdParse { line, reason } => write!(f, "line {line}: {reason}"),
+            RenderError::MissingReference { id } => {
+                write!(f, "{} {} not found", kind(*id), id.inner_id())
+            }
+            RenderError::NothingToDraw { id } => {
+                write!(f, "{} {} has nothing to draw", kind(*id), id.inner_id())
+            }
+            RenderError::EmptySelection => write!(f, "nothing selected to draw"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::Io(e) => Some(e),
+            RenderError::PbfParse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for RenderError {
+    fn from(e: std::io::Error) -> Self {
+        RenderError::Io(e)
+    }
+}
+
+impl From<osmpbfreader::Error> for RenderError {
+    fn from(e: osmpbfreader::Error) -> Self {
+        RenderError::PbfParse(e)
+    }
+}
+
+/// The name of the kind of an object, as used in messages.
+pub fn kind(id: OsmId) -> &'static str {
+    match id {
+        OsmId::Node(_) => "node",
+        OsmId::Way(_) => "way",
+        OsmId::Relation(_) => "relation",
+    }
+}
+
+/// Reads a file of OSM ids, either one per line or as a JSON array.
+pub fn read_ids<T: Ord>(
+    path: &std::path::Path,
+    id: fn(i64) -> T,
+) -> Result<BTreeSet<T>, RenderError> {
+    let content = std::fs::read_to_string(path)?;
+    if path.extension().is_some_and(|e| e == "json") || content.trim_start().starts_with('[') {
+        let ids: Vec<i64> = serde_json::from_str(&content).map_err(|e| RenderError::IdParse {
+            line: e.line(),
+            reason: format!("not a JSON array of ids: {e}"),
+        })?;
+        Ok(ids.into_iter().map(id).collect())
+    } else {
+        content
+            .lines()
+            .enumerate()
+            .map(|(i, l)| {
+                let parsed = l.parse().map_err(|e| RenderError::IdParse {
+                    line: i + 1,
+                    reason: format!("{l:?} isn't an id: {e}"),
+                })?;
+                Ok(id(parsed))
+            })
+            .collect()
+    }
+}
+
+/// Resolves node ids to coordinates, so that node data can live outside the map of objects.
+pub trait NodeSource {
+    /// The latitude and longitude of a node in degrees, if it is known.
+    fn lat_lon(&self, id: NodeId) -> Option<(f64, f64)>;
+
+    /// A node, with its tags if the source has them; by default without any.
+    fn node(&self, id: NodeId) -> Option<Node> {
+        let (lat, lon) = self.lat_lon(id)?;
+        Some(Node {
+            id,
+            tags: Tags::new(),
+            decimicro_lat: (lat * 1e7).round() as i32,
+            decimicro_lon: (lon * 1e7).round() as i32,
+        })
+    }
+}
+
+impl NodeSource for BTreeMap<OsmId, OsmObj> {
+    fn lat_lon(&self, id: NodeId) -> Option<(f64, f64)> {
+        let node = self.get(&OsmId::Node(id))?.node()?;
+        Some((node.lat(), node.lon()))
+    }
+
+    fn node(&self, id: NodeId) -> Option<Node> {
+        self.get(&OsmId::Node(id))?.node().cloned()
+    }
+}
+
+/// The coordinates of the nodes of a way, skipping any missing from `nodes`.
+fn way_nodes<'a>(
+    nodes: &'a impl NodeSource,
+    way: &'a Way,
+) -> impl Iterator<Item = (f64, f64)> + 'a {
+    way.nodes.iter().filter_map(|n| nodes.lat_lon(*n))
+}
+
+/// The length of a way in `projection`.
+pub fn way_length(nodes: &impl NodeSource, way: &Way, projection: &dyn Projection) -> f64 {
+    polyline_length(
+        &way_nodes(nodes, way)
+            .map(|(lat, lon)| projection.project(lat.to_radians(), lon.to_radians()))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// The length of a way in meters along the earth's surface, using the haversine formula.
+pub fn way_geodesic_length(nodes: &impl NodeSource, way: &Way) -> f64 {
+    let points = way_nodes(nodes, way).collect::<Vec<_>>();
+    points.windows(2).map(|w| haversine(w[0], w[1])).sum()
+}
+
+/// The area enclosed by a closed way in `projection`.
+///
+/// Open ways don't enclose anything, so their area is 0.
+pub fn polygon_area(nodes: &impl NodeSource, way: &Way, projection: &dyn Projection) -> f64 {
+    if way.is_open() {
+        return 0.0;
+    }
+    let points = way_nodes(nodes, way)
+        .map(|(lat, lon)| projection.project(lat.to_radians(), lon.to_radians()))
+        .collect::<Vec<_>>();
+    signed_area(&points).abs()
+}
+
+/// The area of a closed ring of projected points, given with its closing point,
+/// positive if it runs clockwise as drawn and negative if it runs counterclockwise.
+pub fn signed_area(ring: &[(f64, f64)]) -> f64 {
+    // Shoelace formula; the closing point repeats the first, so every edge is a window.
+    // Projected y grows downwards, so the sign is the other way around from usual.
+    let twice_area: f64 = ring
+        .windows(2)
+        .map(|w| w[0].0 * w[1].1 - w[1].0 * w[0].1)
+        .sum();
+    twice_area / 2.0
+}
+
+/// The relations and closed ways in `objs` whose area contains the given point,
+/// with the coordinates of their nodes from `nodes`.
+///
+/// A relation's area is made of the rings its member ways form; inner rings are holes in it.
+/// Edges are straight in `projection`.
+pub fn features_containing(
+    objs: &BTreeMap<OsmId, OsmObj>,
+    nodes: &impl NodeSource,
+    lat: f64,
+    lon: f64,
+    projection: &dyn Projection,
+) -> Vec<OsmId> {
+    let point = projection.project(lat.to_radians(), lon.to_radians());
+    let ring_points = |ring: &[NodeId]| {
+        ring.iter()
+            .filter_map(|n| nodes.node(*n))
+            .map(|n| project_node(&n, projection))
+            .collect::<Vec<_>>()
+    };
+    objs.values()
+        .filter(|o| match o {
+            OsmObj::Way(way) => {
+                way.nodes.len() >= 4
+                    && way.is_closed()
+                    && ring_contains(&ring_points(&way.nodes), point)
+            }
+            // Even-odd over all rings at once keeps points in holes outside.
+            OsmObj::Relation(rel) => {
+                assemble_rings(objs, nodes, rel, projection)
+                    .iter()
+                    .filter(|ring| ring_contains(&ring.points, point))
+                    .count()
+                    % 2
+                    == 1
+            }
+            OsmObj::Node(_) => false,
+        })
+        .map(OsmObj::id)
+        .collect()
+}
+
+/// A closed ring formed by member ways of a relation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ring {
+    /// The nodes of the ring, with the first repeated at the end.
+    pub nodes: Vec<NodeId>,
+    /// The points of the ring's nodes in the projection it was assembled in,
+    /// skipping any missing from the data.
+    pub points: Vec<(f64, f64)>,
+    /// The ways the ring was stitched together from.
+    pub ways: Vec<WayId>,
+    /// Whether the ring bounds the area or a hole in it, by the role of its ways.
+    pub role: RingRole,
+}
+
+/// The part a ring plays in a multipolygon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingRole {
+    Outer,
+    /// A hole, from ways with the `inner` role.
+    Inner,
+}
+
+/// The part a member with `role` plays in the rings of a relation, or `None` if it has none.
+///
+/// In multipolygons and boundaries, members with an empty role are taken as `outer`,
+/// as is common in older data, while other roles such as `subarea` or `label` aren't
+/// part of the area. Other relations have no ring roles, so any member but an `inner`
+/// one is outer.
+pub fn ring_role(rel: &Relation, role: &str) -> Option<RingRole> {
+    match role {
+        "inner" => Some(RingRole::Inner),
+        "outer" | "" => Some(RingRole::Outer),
+        _ if is_area_relation(rel) => None,
+        _ => Some(RingRole::Outer),
+    }
+}
+
+/// Whether a relation describes an area made of the rings of its member ways.
+pub fn is_area_relation(rel: &Relation) -> bool {
+    rel.tags
+        .get("type")
+        .is_some_and(|t| t == "multipolygon" || t == "boundary")
+}
+
+/// Stitches the member ways of a relation in `objs` into closed rings,
+/// with the coordinates of their nodes from `nodes`.
+///
+/// Ways are joined end to end with ways of the same role, reversing them where needed;
+/// chains that can't be closed are dropped. The rings' points are projected with `projection`.
+pub fn assemble_rings(
+    objs: &BTreeMap<OsmId, OsmObj>,
+    nodes: &impl NodeSource,
+    rel: &Relation,
+    projection: &dyn Projection,
+) -> Vec<Ring> {
+    let mut open = Vec::new();
+    let mut rings = Vec::new();
+    for (way, role) in rel.refs.iter().filter_map(|r| {
+        let role = ring_role(rel, &r.role)?;
+        let way = objs.get(&r.member)?.way().filter(|w| w.nodes.len() >= 2)?;
+        Some((way, role))
+    }) {
+        let ring = Ring {
+            nodes: way.nodes.clone(),
+            points: Vec::new(),
+            ways: vec![way.id],
+            role,
+        };
+        if way.is_closed() {
+            rings.push(ring);
+        } else {
+            open.push(ring);
+        }
+    }
+    while let Some(mut ring) = open.pop() {
+        while ring.nodes.first() != ring.nodes.last() {
+            let end = *ring.nodes.last().unwrap();
+            let Some(i) = open.iter().position(|w: &Ring| {
+                w.role == ring.role
+                    && (w.nodes.first() == Some(&end) || w.nodes.last() == Some(&end))
+            }) else {
+                break;
+            };
+            let mut next = open.swap_remove(i);
+            if next.nodes.first() != Some(&end) {
+                next.nodes.reverse();
+            }
+            ring.nodes.extend(next.nodes.into_iter().skip(1));
+            ring.ways.extend(next.ways);
+        }
+        if ring.nodes.first() == ring.nodes.last() && ring.nodes.len() >= 4 {
+            rings.push(ring);
+        }
+    }
+    for ring in &mut rings {
+        ring.points = ring
+            .nodes
+            .iter()
+            .filter_map(|n| nodes.node(*n))
+            .map(|n| project_node(&n, projection))
+            .collect();
+    }
+    rings
+}
+
+/// Whether a closed ring of projected points contains a point, by ray casting.
+pub fn ring_contains(ring: &[(f64, f64)], (x, y): (f64, f64)) -> bool {
+    ring.windows(2)
+        .filter(|e| {
+            let ((x0, y0), (x1, y1)) = (e[0], e[1]);
+            (y0 > y) != (y1 > y) && x < x0 + (y - y0) / (y1 - y0) * (x1 - x0)
+        })
+        .count()
+        % 2
+        == 1
+}
+
+/// The convex hull of a set of points, from the leftmost one and counterclockwise
+/// if y grows upwards, without repeating the first point at the end.
+///
+/// Uses Andrew's monotone chain; points on the hull's edges aren't part of it.
+pub fn convex_hull(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+    let mut hull = Vec::<(f64, f64)>::with_capacity(2 * points.len());
+    // The lower chain from left to right, then the upper one back.
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let start = hull.len();
+        for p in pass {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // Each chain ends where the other begins.
+        hull.pop();
+    }
+    hull
+}
+
+/// The rings outlining a set of points more closely than their convex hull, as an alpha shape:
+/// the Delaunay triangles no wider around than `radius`, merged, without the closing points.
+///
+/// Points further than about twice `radius` apart end up in separate rings,
+/// and gaps that wide within them in holes.
+/// The triangulation takes time growing with the square of the number of points.
+pub fn alpha_shape(points: &[(f64, f64)], radius: f64) -> Vec<Vec<(f64, f64)>> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+    let n = points.len();
+    if n < 3 {
+        return Vec::new();
+    }
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+    // Bowyer-Watson, starting from a triangle around all the points.
+    // Triangles stay counterclockwise as `cross` goes, which the circumcircle test relies on.
+    let (x0, x1) = (points[0].0, points[n - 1].0);
+    let (y0, y1) = points
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| {
+            (lo.min(p.1), hi.max(p.1))
+        });
+    let (cx, cy) = ((x0 + x1) / 2.0, (y0 + y1) / 2.0);
+    let m = (x1 - x0).max(y1 - y0).max(f64::MIN_POSITIVE) * 20.0;
+    points.extend([(cx - m, cy - m), (cx + m, cy - m), (cx, cy + m)]);
+    let circumscribes = |t: &[usize; 3], p: (f64, f64)| {
+        let [a, b, c] = t.map(|i| (points[i].0 - p.0, points[i].1 - p.1));
+        let det = (a.0 * a.0 + a.1 * a.1) * (b.0 * c.1 - c.0 * b.1)
+            - (b.0 * b.0 + b.1 * b.1) * (a.0 * c.1 - c.0 * a.1)
+            + (c.0 * c.0 + c.1 * c.1) * (a.0 * b.1 - b.0 * a.1);
+        det > 0.0
+    };
+    let mut triangles = vec![[n, n + 1, n + 2]];
+    for (i, &p) in points.iter().enumerate().take(n) {
+        let (bad, good) = triangles
+            .into_iter()
+            .partition::<Vec<_>, _>(|t| circumscribes(t, p));
+        triangles = good;
+        // The edges around the hole left by the removed triangles, each joined to the new point.
+        let mut edges = BTreeMap::<(usize, usize), usize>::new();
+        for [a, b, c] in &bad {
+            for (u, v) in [(*a, *b), (*b, *c), (*c, *a)] {
+                *edges.entry((u.min(v), u.max(v))).or_default() += 1;
+            }
+        }
+        for [a, b, c] in &bad {
+            for (u, v) in [(*a, *b), (*b, *c), (*c, *a)] {
+                if edges[&(u.min(v), u.max(v))] == 1 {
+                    triangles.push([u, v, i]);
+                }
+            }
+        }
+    }
+    let mut boundary = BTreeMap::<usize, Vec<usize>>::new();
+    let kept = triangles
+        .into_iter()
+        .filter(|t| t.iter().all(|&i| i < n))
+        .filter(|t| {
+            let [a, b, c] = t.map(|i| points[i]);
+            let sides = (a.0 - b.0).hypot(a.1 - b.1)
+                * (b.0 - c.0).hypot(b.1 - c.1)
+                * (c.0 - a.0).hypot(c.1 - a.1);
+            let area = cross(a, b, c).abs() / 2.0;
+            area > 0.0 && sides / (4.0 * area) <= radius
+        })
+        .collect::<Vec<_>>();
+    let edges = kept
+        .iter()
+        .flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)])
+        .collect::<BTreeSet<_>>();
+    // Edges of just one triangle are on the outline, running the same way around as it.
+    for &(u, v) in &edges {
+        if !edges.contains(&(v, u)) {
+            boundary.entry(u).or_default().push(v);
+        }
+    }
+    let mut rings = Vec::new();
+    while let Some((&start, _)) = boundary.iter().next() {
+        let mut ring = Vec::new();
+        let mut at = start;
+        // Where rings touch at a point, the walk may go around both, which draws the same.
+        while let Some(next) = boundary.get_mut(&at).and_then(Vec::pop) {
+            if boundary[&at].is_empty() {
+                boundary.remove(&at);
+            }
+            ring.push(points[at]);
+            at = next;
+            if at == start {
+                break;
+            }
+        }
+        rings.push(ring);
+    }
+    rings
+}
+
+/// Whether a closed ring, given with its closing point, crosses itself.
+///
+/// Only edges that properly cross count, not ones that merely touch.
+pub fn self_intersects(ring: &[(f64, f64)]) -> bool {
+    let side = |a: (f64, f64), b: (f64, f64), p: (f64, f64)| {
+        ((b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)).signum()
+    };
+    let crosses = |(a, b): ((f64, f64), (f64, f64)), (c, d): ((f64, f64), (f64, f64))| {
+        let (ab_c, ab_d) = (side(a, b, c), side(a, b, d));
+        let (cd_a, cd_b) = (side(c, d, a), side(c, d, b));
+        ab_c * ab_d < 0.0 && cd_a * cd_b < 0.0
+    };
+    let edges = ring.windows(2).map(|e| (e[0], e[1])).collect::<Vec<_>>();
+    // Neighbouring edges share a point, including the last and first of the ring.
+    (0..edges.len()).any(|i| {
+        (i + 2..edges.len())
+            .filter(|&j| !(i == 0 && j == edges.len() - 1))
+            .any(|j| crosses(edges[i], edges[j]))
+    })
+}
+
+/// The distance in meters between two points given as latitude and longitude in degrees.
+fn haversine((lat_a, lon_a): (f64, f64), (lat_b, lon_b): (f64, f64)) -> f64 {
+    let (lat_a, lat_b) = (lat_a.to_radians(), lat_b.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (lon_b - lon_a).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+/// The length of a polyline.
+pub fn polyline_length(points: &[(f64, f64)]) -> f64 {
+    points
+        .windows(2)
+        .map(|w| (w[1].0 - w[0].0).hypot(w[1].1 - w[0].1))
+        .sum()
+}
+
+/// How sharply a polyline turns at each of its points, in radians from 0 (straight on) to π.
+///
+/// The ends don't turn, and neither do points that don't move on from the one before.
+pub fn turning_angles(points: &[(f64, f64)]) -> Vec<f64> {
+    let mut angles = vec![0.0; points.len()];
+    for (i, w) in points.windows(3).enumerate() {
+        let (a, b) = (
+            (w[1].0 - w[0].0, w[1].1 - w[0].1),
+            (w[2].0 - w[1].0, w[2].1 - w[1].1),
+        );
+        if a == (0.0, 0.0) || b == (0.0, 0.0) {
+            continue;
+        }
+        let cross = a.0 * b.1 - a.1 * b.0;
+        let dot = a.0 * b.0 + a.1 * b.1;
+        angles[i + 1] = cross.atan2(dot).abs();
+    }
+    angles
+}
+
+/// The outline of the area within `half_width` of a polyline, as a ring without its closing point.
+///
+/// Corners are mitered, or beveled where they are too sharp for a short miter;
+/// the ends are cut off square at the first and last points.
+pub fn buffer(points: &[(f64, f64)], half_width: f64) -> Vec<(f64, f64)> {
+    // Miters longer than this many half widths are beveled instead.
+    const MITER_LIMIT: f64 = 4.0;
+    let normals = points
+        .windows(2)
+        .filter_map(|w| {
+            let (dx, dy) = (w[1].0 - w[0].0, w[1].1 - w[0].1);
+            let length = dx.hypot(dy);
+            // Left of the direction of travel, in SVG's downward y.
+            (length > 0.0).then(|| (dy / length, -dx / length))
+        })
+        .collect::<Vec<_>>();
+    if normals.is_empty() {
+        return Vec::new();
+    }
+    let side = |sign: f64| {
+        let mut outline = Vec::new();
+        let offset =
+            |p: (f64, f64), n: (f64, f64), d: f64| (p.0 + sign * n.0 * d, p.1 + sign * n.1 * d);
+        outline.push(offset(points[0], normals[0], half_width));
+        for (i, n) in normals.windows(2).enumerate() {
+            let (n0, n1) = (n[0], n[1]);
+            let p = points[i + 1];
+            let (mx, my) = (n0.0 + n1.0, n0.1 + n1.1);
+            let length = mx.hypot(my);
+            // The cosine of half the angle between the segments.
+            let cos = length / 2.0;
+            if cos > 1.0 / MITER_LIMIT {
+                outline.push(offset(p, (mx / length, my / length), half_width / cos));
+            } else {
+                outline.push(offset(p, n0, half_width));
+                outline.push(offset(p, n1, half_width));
+            }
+        }
+        outline.push(offset(
+            points[points.len() - 1],
+            normals[normals.len() - 1],
+            half_width,
+        ));
+        outline
+    };
+    let mut outline = side(1.0);
+    outline.extend(side(-1.0).into_iter().rev());
+    outline
+}
+
+/// Simplifies a polyline with the Douglas-Peucker algorithm, keeping its endpoints.
+pub fn simplify(points: Vec<(f64, f64)>, tolerance: f64) -> Vec<(f64, f64)> {
+    let keep = simplify_mask(&points, tolerance);
+    kept(points, keep)
+}
+
+/// Simplifies a polyline with the Visvalingam-Whyatt algorithm, keeping its endpoints.
+pub fn visvalingam(points: Vec<(f64, f64)>, tolerance: f64) -> Vec<(f64, f64)> {
+    let keep = visvalingam_mask(&points, tolerance);
+    kept(points, keep)
+}
+
+/// The points whose entry in `keep` is true.
+fn kept(points: Vec<(f64, f64)>, keep: Vec<bool>) -> Vec<(f64, f64)> {
+    points
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(p, keep)| keep.then_some(p))
+        .collect()
+}
+
+/// Which points of a polyline [`simplify`] keeps.
+pub fn simplify_mask(points: &[(f64, f64)], tolerance: f64) -> Vec<bool> {
+    // Both endpoints are always kept.
+    let mut keep = (0..points.len())
+        .map(|i| i == 0 || i == points.len() - 1)
+        .collect::<Vec<_>>();
+    douglas_peucker(points, tolerance, &mut keep);
+    keep
+}
+
+/// Marks the points between the first and last that must be kept to stay within `tolerance`.
+fn douglas_peucker(points: &[(f64, f64)], tolerance: f64, keep: &mut [bool]) {
+    if points.len() < 3 {
+        return;
+    }
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let (index, distance) = points[1..points.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i + 1, segment_distance(*p, first, last)))
+        .fold((0, 0.0), |max, d| if d.1 > max.1 { d } else { max });
+    if distance > tolerance {
+        keep[index] = true;
+        douglas_peucker(&points[..=index], tolerance, &mut keep[..=index]);
+        douglas_peucker(&points[index..], tolerance, &mut keep[index..]);
+    }
+}
+
+/// Which points of a polyline the Visvalingam-Whyatt algorithm keeps, keeping its endpoints.
+///
+/// Points are removed in order of the area of the triangle they form with their remaining
+/// neighbours, until every remaining point's triangle has an area of at least `tolerance`.
+pub fn visvalingam_mask(points: &[(f64, f64)], tolerance: f64) -> Vec<bool> {
+    let mut keep = vec![true; points.len()];
+    // The neighbours of each point among those still kept.
+    let mut prev = (0..points.len())
+        .map(|i| i.wrapping_sub(1))
+        .collect::<Vec<_>>();
+    let mut next = (1..=points.len()).collect::<Vec<_>>();
+    let area = |i: usize, prev: &[usize], next: &[usize]| {
+        let (a, b, c) = (points[prev[i]], points[i], points[next[i]]);
+        ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs() / 2.0
+    };
+    while let Some((i, smallest)) = (1..points.len().saturating_sub(1))
+        .filter(|&i| keep[i])
+        .map(|i| (i, area(i, &prev, &next)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+    {
+        if smallest >= tolerance {
+            break;
+        }
+        keep[i] = false;
+        next[prev[i]] = next[i];
+        prev[next[i]] = prev[i];
+    }
+    keep
+}
+
+/// The distance from `p` to the segment from `a` to `b`.
+fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+}
+
+/// Projects a node to SVG coordinates.
+pub fn project_node(node: &Node, projection: &dyn Projection) -> (f64, f64) {
+    projection.project(node.lat().to_radians(), node.lon().to_radians())
+}
+
+/// The projected length of a distance in meters at a latitude in degrees.
+pub fn meters_to_projected(meters: f64, lat: f64) -> f64 {
+    meters / EARTH_RADIUS * SCALE / lat.to_radians().cos()
+}
+
+/// A map projection to SVG coordinates, with y pointing down.
+pub trait Projection {
+    /// Projects latitude and longitude in radians.
+    fn project(&self, lat: f64, lon: f64) -> (f64, f64);
+}
+
+/// The Web Mercator projection (EPSG:3857).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WebMercator;
+
+impl Projection for WebMercator {
+    fn project(&self, lat: f64, lon: f64) -> (f64, f64) {
+        (lon * SCALE, (-lat / 2.0 + PI / 4.0).tan().ln() * SCALE)
+    }
+}
+
+/// The equirectangular projection, with latitude and longitude as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Equirectangular;
+
+impl Projection for Equirectangular {
+    fn project(&self, lat: f64, lon: f64) -> (f64, f64) {
+        (lon * SCALE, -lat * SCALE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use osmpbfreader::{Ref, RelationId};
+
+    /// Maps degrees to themselves, to check what goes through a projection.
+    struct Degrees;
+
+    impl Projection for Degrees {
+        fn project(&self, lat: f64, lon: f64) -> (f64, f64) {
+            (lon.to_degrees(), lat.to_degrees())
+        }
+    }
+
+    /// Nodes kept apart from any objects, as an indexed source would.
+    struct Coordinates(BTreeMap<NodeId, (f64, f64)>);
+
+    impl NodeSource for Coordinates {
+        fn lat_lon(&self, id: NodeId) -> Option<(f64, f64)> {
+            self.0.get(&id).copied()
+        }
+    }
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-6 && (a.1 - b.1).abs() < 1e-6
+    }
+
+    fn node(id: i64, lat: f64, lon: f64) -> OsmObj {
+        OsmObj::Node(Node {
+            id: NodeId(id),
+            tags: Tags::new(),
+            decimicro_lat: (lat * 1e7).round() as i32,
+            decimicro_lon: (lon * 1e7).round() as i32,
+        })
+    }
+
+    fn way(id: i64, nodes: &[i64]) -> Way {
+        Way {
+            id: WayId(id),
+            tags: Tags::new(),
+            nodes: nodes.iter().map(|&n| NodeId(n)).collect(),
+        }
+    }
+
+    fn multipolygon(members: &[(i64, &str)]) -> Relation {
+        let mut tags = Tags::new();
+        tags.insert("type".into(), "multipolygon".into());
+        Relation {
+            id: RelationId(1),
+            tags,
+            refs: members
+                .iter()
+                .map(|&(w, role)| Ref {
+                    member: OsmId::Way(WayId(w)),
+                    role: role.into(),
+                })
+                .collect(),
+        }
+    }
+
+    /// A unit square of nodes 1 to 4 counterclockwise from the origin, in degrees,
+    /// with a smaller square of nodes 5 to 8 inside it.
+    fn squares() -> BTreeMap<OsmId, OsmObj> {
+        [
+            node(1, 0.0, 0.0),
+            node(2, 0.0, 1.0),
+            node(3, 1.0, 1.0),
+            node(4, 1.0, 0.0),
+            node(5, 0.25, 0.25),
+            node(6, 0.25, 0.75),
+            node(7, 0.75, 0.75),
+            node(8, 0.75, 0.25),
+        ]
+        .into_iter()
+        .map(|n| (n.id(), n))
+        .collect()
+    }
+
+    #[test]
+    fn equirectangular_keeps_degrees_apart_evenly() {
+        let (x, y) = Equirectangular.project(0.5, 0.25);
+        assert!(close((x, y), (0.25 * SCALE, -0.5 * SCALE)));
+    }
+
+    #[test]
+    fn web_mercator_stretches_away_from_the_equator() {
+        assert!(close(WebMercator.project(0.0, 1.0), (SCALE, 0.0)));
+        let (_, north) = WebMercator.project(1.0, 0.0);
+        let (_, south) = WebMercator.project(-1.0, 0.0);
+        assert!(north < -SCALE);
+        assert!((north + south).abs() < 1e-6);
+    }
+
+    #[test]
+    fn project_node_uses_the_projection_given() {
+        let n = node(1, 50.0, 10.0);
+        assert!(close(
+            project_node(n.node().unwrap(), &Degrees),
+            (10.0, 50.0)
+        ));
+    }
+
+    #[test]
+    fn signed_area_is_positive_clockwise_as_drawn() {
+        // y grows downwards, so this runs clockwise on screen.
+        let clockwise = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0), (0.0, 0.0)];
+        assert_eq!(signed_area(&clockwise), 4.0);
+        let counterclockwise = clockwise.iter().rev().copied().collect::<Vec<_>>();
+        assert_eq!(signed_area(&counterclockwise), -4.0);
+    }
+
+    #[test]
+    fn polygon_area_is_in_the_projection_given() {
+        let objs = squares();
+        let square = way(10, &[1, 2, 3, 4, 1]);
+        assert!((polygon_area(&objs, &square, &Degrees) - 1.0).abs() < 1e-6);
+        let area = polygon_area(&objs, &square, &Equirectangular);
+        assert!((area - (SCALE * PI / 180.0).powi(2)).abs() < 1e-3);
+        assert_eq!(polygon_area(&objs, &way(11, &[1, 2, 3]), &Degrees), 0.0);
+    }
+
+    #[test]
+    fn node_sources_other_than_the_objects_are_used() {
+        let coordinates = Coordinates(
+            [(NodeId(1), (0.0, 0.0)), (NodeId(2), (0.0, 3.0))]
+                .into_iter()
+                .collect(),
+        );
+        let line = way(10, &[1, 2, 99]);
+        assert!((way_length(&coordinates, &line, &Degrees) - 3.0).abs() < 1e-6);
+        let n = coordinates.node(NodeId(2)).unwrap();
+        assert_eq!((n.id, n.lat(), n.lon()), (NodeId(2), 0.0, 3.0));
+        assert!(coordinates.node(NodeId(99)).is_none());
+    }
+
+    #[test]
+    fn assemble_rings_joins_ways_end_to_end() {
+        let mut objs = squares();
+        // The second way runs backwards, and the last can't be closed.
+        for w in [
+            way(10, &[1, 2, 3]),
+            way(11, &[1, 4, 3]),
+            way(12, &[5, 6, 7, 8, 5]),
+            way(13, &[5, 7]),
+        ] {
+            objs.insert(OsmId::Way(w.id), OsmObj::Way(w));
+        }
+        let rel = multipolygon(&[(10, "outer"), (11, ""), (12, "inner"), (13, "inner")]);
+        let rings = assemble_rings(&objs, &objs, &rel, &Degrees);
+        assert_eq!(rings.len(), 2);
+        let inner = rings.iter().find(|r| r.role == RingRole::Inner).unwrap();
+        assert_eq!(inner.ways, [WayId(12)]);
+        let outer = rings.iter().find(|r| r.role == RingRole::Outer).unwrap();
+        assert_eq!(outer.nodes.first(), outer.nodes.last());
+        assert_eq!(outer.nodes.len(), 5);
+        let mut ways = outer.ways.clone();
+        ways.sort();
+        assert_eq!(ways, [WayId(10), WayId(11)]);
+        assert!(close(outer.points[0], (0.0, 0.0)));
+        assert!(ring_contains(&outer.points, (0.5, 0.1)));
+    }
+
+    #[test]
+    fn two_outer_ways_sharing_endpoints_make_one_ring() {
+        let mut objs = squares();
+        for w in [way(10, &[1, 2, 3]), way(11, &[3, 4, 1])] {
+            objs.insert(OsmId::Way(w.id), OsmObj::Way(w));
+        }
+        let rel = multipolygon(&[(10, "outer"), (11, "outer")]);
+        let rings = assemble_rings(&objs, &objs, &rel, &Degrees);
+        assert_eq!(rings.len(), 1);
+        assert_eq!(rings[0].role, RingRole::Outer);
+        let mut ways = rings[0].ways.clone();
+        ways.sort();
+        assert_eq!(ways, [WayId(10), WayId(11)]);
+        let nodes = &rings[0].nodes;
+        assert_eq!((nodes.len(), nodes.first()), (5, nodes.last()));
+        assert_eq!(rings[0].points.len(), 5);
+        assert!(ring_contains(&rings[0].points, (0.5, 0.5)));
+    }
+
+    #[test]
+    fn empty_roles_in_multipolygons_are_outer() {
+        let mut objs = squares();
+        for w in [way(10, &[1, 2, 3, 4, 1]), way(11, &[5, 6, 7, 8, 5])] {
+            objs.insert(OsmId::Way(w.id), OsmObj::Way(w));
+        }
+        let rel = multipolygon(&[(10, ""), (11, "label")]);
+        assert_eq!(ring_role(&rel, ""), Some(RingRole::Outer));
+        assert_eq!(ring_role(&rel, "label"), None);
+        let rings = assemble_rings(&objs, &objs, &rel, &Degrees);
+        assert_eq!(rings.len(), 1);
+        assert_eq!(
+            (rings[0].role, &rings[0].ways[..]),
+            (RingRole::Outer, &[WayId(10)][..])
+        );
+    }
+
+    #[test]
+    fn features_containing_leaves_holes_out() {
+        let mut objs = squares();
+        for w in [way(10, &[1, 2, 3, 4, 1]), way(12, &[5, 6, 7, 8, 5])] {
+            objs.insert(OsmId::Way(w.id), OsmObj::Way(w));
+        }
+        let rel = multipolygon(&[(10, "outer"), (12, "inner")]);
+        objs.insert(OsmId::Relation(rel.id), OsmObj::Relation(rel));
+        let rel = OsmId::Relation(RelationId(1));
+        let at = |lat, lon| features_containing(&objs, &objs, lat, lon, &Equirectangular);
+        assert!(at(0.1, 0.1).contains(&rel));
+        assert!(!at(0.5, 0.5).contains(&rel));
+        assert!(at(0.5, 0.5).contains(&OsmId::Way(WayId(12))));
+    }
+
+    #[test]
+    fn convex_hull_keeps_only_the_corners() {
+        let points = [
+            (1.0, 1.0),
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 2.0),
+            (0.0, 2.0),
+            (0.0, 2.0),
+        ];
+        assert_eq!(
+            convex_hull(&points),
+            [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]
+        );
+        assert_eq!(convex_hull(&[(1.0, 1.0), (1.0, 1.0)]), [(1.0, 1.0)]);
+    }
+
+    #[test]
+    fn alpha_shape_separates_far_apart_points() {
+        let cluster = |x: f64| [(x, 0.0), (x + 1.0, 0.0), (x + 1.0, 1.0), (x, 1.0)];
+        let points = cluster(0.0)
+            .into_iter()
+            .chain(cluster(100.0))
+            .collect::<Vec<_>>();
+        let rings = alpha_shape(&points, 2.0);
+        assert_eq!(rings.len(), 2);
+        for p in &points {
+            assert_eq!(rings.iter().filter(|r| r.contains(p)).count(), 1);
+        }
+        // A radius wide enough to span the gap makes one ring of them.
+        assert_eq!(alpha_shape(&points, 1000.0).len(), 1);
+        assert!(alpha_shape(&points[..2], 2.0).is_empty());
+    }
+
+    #[test]
+    fn buffer_of_a_segment_is_a_rectangle_around_it() {
+        let outline = buffer(&[(0.0, 0.0), (10.0, 0.0)], 1.0);
+        assert_eq!(outline.len(), 4);
+        let mut ring = outline.clone();
+        ring.push(ring[0]);
+        assert!((signed_area(&ring).abs() - 20.0).abs() < 1e-9);
+        let xs = outline.iter().map(|p| p.0);
+        let ys = outline.iter().map(|p| p.1);
+        let (min_x, max_x) = (
+            xs.clone().fold(f64::MAX, f64::min),
+            xs.fold(f64::MIN, f64::max),
+        );
+        let (min_y, max_y) = (
+            ys.clone().fold(f64::MAX, f64::min),
+            ys.fold(f64::MIN, f64::max),
+        );
+        assert_eq!((min_x, max_x, min_y, max_y), (0.0, 10.0, -1.0, 1.0));
+        // A right angle is mitered, out to the corner of both sides' widths.
+        let outline = buffer(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)], 1.0);
+        assert_eq!(outline.len(), 6);
+        assert!(outline.iter().any(|&p| close(p, (11.0, -1.0))));
+        assert!(outline.iter().any(|&p| close(p, (9.0, 1.0))));
+        assert!(buffer(&[(1.0, 1.0), (1.0, 1.0)], 1.0).is_empty());
+    }
+
+    #[test]
+    fn simplify_drops_points_within_the_tolerance() {
+        let line = vec![(0.0, 0.0), (1.0, 0.1), (2.0, 0.0), (3.0, 5.0), (4.0, 0.0)];
+        assert_eq!(
+            simplify(line.clone(), 0.5),
+            [(0.0, 0.0), (2.0, 0.0), (3.0, 5.0), (4.0, 0.0)]
+        );
+        assert_eq!(simplify(line.clone(), 10.0), [(0.0, 0.0), (4.0, 0.0)]);
+        assert_eq!(simplify(line.clone(), 0.01), line);
+    }
+
+    #[test]
+    fn visvalingam_drops_points_with_small_triangles() {
+        let line = vec![(0.0, 0.0), (1.0, 0.1), (2.0, 0.0), (3.0, 5.0), (4.0, 0.0)];
+        assert_eq!(
+            visvalingam(line.clone(), 1.0),
+            [(0.0, 0.0), (2.0, 0.0), (3.0, 5.0), (4.0, 0.0)]
+        );
+        assert_eq!(visvalingam(line.clone(), 100.0), [(0.0, 0.0), (4.0, 0.0)]);
+        assert_eq!(visvalingam_mask(&line, 0.01), [true; 5]);
+    }
+
+    /// Writes `content` to a file of its own in the temporary directory.
+    fn id_file(name: &str, content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("osm-rels-svg-{}-{name}", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn read_ids_reads_lines_and_json() {
+        let lines = id_file("lines.txt", "3\n1\n3\n");
+        let json = id_file("array.json", "[2, 1]");
+        assert_eq!(
+            read_ids(&lines, WayId).unwrap(),
+            [WayId(1), WayId(3)].into()
+        );
+        assert_eq!(
+            read_ids(&json, RelationId).unwrap(),
+            [RelationId(1), RelationId(2)].into()
+        );
+        std::fs::remove_file(lines).unwrap();
+        std::fs::remove_file(json).unwrap();
+    }
+
+    #[test]
+    fn read_ids_says_which_line_is_wrong() {
+        let path = id_file("bad.txt", "1\nx\n");
+        let error = read_ids(&path, WayId).unwrap_err();
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(error, RenderError::IdParse { line: 2, .. }));
+        let missing = std::env::temp_dir().join("osm-rels-svg-no-such-file.txt");
+        assert!(matches!(read_ids(&missing, WayId), Err(RenderError::Io(_))));
+    }
+}
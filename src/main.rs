@@ -1,22 +1,131 @@
 use std::{
+    cell::Cell,
     collections::{BTreeMap, BTreeSet},
     error::Error,
     f64::consts::PI,
     fs::File,
-    io::{self, stderr, stdout, BufRead, BufReader, Write},
-    ops::Range,
+    io::{stdout, Read, Seek, Write},
+    ops::{Range, RangeInclusive},
 };
 
-use clap::Parser;
-use osmpbfreader::{Node, OsmId, OsmObj, OsmPbfReader, Relation, RelationId, Tags, Way, WayId};
+use base64::Engine;
+use clap::{Parser, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
+use osm_rels_svg::{
+    alpha_shape, assemble_rings, buffer, convex_hull, is_area_relation, kind, meters_to_projected,
+    polygon_area, polyline_length, read_ids, ring_contains, self_intersects, signed_area, simplify,
+    simplify_mask, turning_angles, visvalingam, visvalingam_mask, Equirectangular, NodeSource,
+    Projection, RenderError, Ring, RingRole, WebMercator, SCALE,
+};
+use osmpbfreader::{
+    osmformat::Info, Node, NodeId, OsmId, OsmObj, OsmPbfReader, Relation, RelationId, Tags, Way,
+    WayId,
+};
+use resvg::{tiny_skia, usvg};
+use serde_json::json;
 use svg::{
-    node::element::{path::Data, Group, Path},
+    node::element::{
+        path::Data, Anchor, Animate, Circle, ClipPath, Definitions, Filter, FilterEffectDropShadow,
+        Group, LinearGradient, Path, Rectangle, Stop, Style, Symbol, Text, Use, SVG,
+    },
     Document,
 };
 
-const SCALE: f64 = 6371.0 * 100.0;
+const STROKE_WIDTH: f64 = 0.0000035 * SCALE;
+const READING_TEMPLATE: &str = "reading {wide_bar} {binary_bytes}/{binary_total_bytes}";
+const RENDERING_TEMPLATE: &str = "rendering {wide_bar} {pos}/{len}";
+const FONT_SIZE: f64 = 0.00004 * SCALE;
+/// The most lines `--graticule` draws, past which its spacing is too fine to be worth drawing.
+const MAX_GRATICULE_LINES: usize = 1000;
+const POINT_MARGIN: f64 = 10.0 * STROKE_WIDTH;
+/// The sphere Web Mercator coordinates are measured on, in meters.
+const WEB_MERCATOR_RADIUS: f64 = 6_378_137.0;
+/// The opacity of a member relation relative to its parent, for `--depth-fade`.
+const DEPTH_FADE: f64 = 0.7;
+/// The colors of straight and of sharply turning stretches of way, for `--curvature-color`.
+const CURVATURE_COLORS: (Rgb, Rgb) = (Rgb(0x20, 0x60, 0xe0), Rgb(0xe0, 0x30, 0x10));
+/// How many shades `--curvature-color` uses, so that stretches of similar curvature share a path.
+const CURVATURE_STEPS: f64 = 8.0;
+/// The length of the dashes of `--flow-animate`, and of the gaps between them.
+const FLOW_DASH: f64 = 0.00002 * SCALE;
+const FLOW_GAP: f64 = 0.00001 * SCALE;
+/// The opacity of every stroke with `--heatmap`, low enough for a few overlaps to add up.
+const HEATMAP_OPACITY: f64 = 0.15;
+/// The colors of clockwise and of counterclockwise closed ways, for `--show-winding`.
+const WINDING_COLORS: (Rgb, Rgb) = (Rgb(0xe0, 0x00, 0x00), Rgb(0x00, 0x50, 0xe0));
+/// The icons of `--icons`, with the tags they stand for and their glyph within a 12 unit circle.
+const ICONS: [(&str, &[&str], &str); 12] = [
+    (
+        "icon-parking",
+        &["amenity=parking"],
+        "M-2,4 V-4 H1 A2,2 0 0 1 1,0 H-2",
+    ),
+    (
+        "icon-medical",
+        &["amenity=hospital", "amenity=pharmacy", "amenity=clinic"],
+        "M0,-4 V4 M-4,0 H4",
+    ),
+    (
+        "icon-fuel",
+        &["amenity=fuel"],
+        "M-3,4 V-4 H2 V4 M-3,-1 H2 M2,-2 L4,0 V3",
+    ),
+    (
+        "icon-cafe",
+        &["amenity=cafe"],
+        "M-3,-2 H2 V1 A2.5,2.5 0 0 1 -3,1 Z M2,-1 A1.5,1.5 0 0 1 2,2",
+    ),
+    (
+        "icon-restaurant",
+        &["amenity=restaurant", "amenity=fast_food"],
+        "M-2,-4 V4 M-3.5,-4 V-1 H-0.5 V-4 M2,4 V-4 A2,3 0 0 1 2,1",
+    ),
+    (
+        "icon-toilets",
+        &["amenity=toilets"],
+        "M-2,-4 V-3 M2,-4 V-3 M-2,-1 V4 M2,-1 L3.5,3 H0.5 Z",
+    ),
+    (
+        "icon-water",
+        &["amenity=drinking_water"],
+        "M0,-4 C2,-1 3,0 3,1.5 A3,3 0 0 1 -3,1.5 C-3,0 -2,-1 0,-4 Z",
+    ),
+    (
+        "icon-shop",
+        &["shop=supermarket", "shop=convenience"],
+        "M-4,-3 H-2.5 L-1,2 H3 L4,-1.5 H-2",
+    ),
+    (
+        "icon-hotel",
+        &["tourism=hotel", "tourism=hostel", "tourism=guest_house"],
+        "M-4,-3 V3 M-4,1 H4 V3 M-1,-1 H4 V1",
+    ),
+    (
+        "icon-information",
+        &["tourism=information"],
+        "M0,-3.5 V-2.5 M0,-1 V3.5",
+    ),
+    ("icon-viewpoint", &["tourism=viewpoint"], "M0,-4 L4,3 H-4 Z"),
+    (
+        "icon-museum",
+        &["tourism=museum"],
+        "M-4,3 H4 M-3,2 V-1 M0,2 V-1 M3,2 V-1 M-4,-1 L0,-4 L4,-1 Z",
+    ),
+];
+const ATTRIBUTION: &str = "© OpenStreetMap contributors, ODbL";
+/// Colors for `--debug-colors`, distinct enough to tell neighbouring ways apart.
+const DEBUG_PALETTE: [Rgb; 8] = [
+    Rgb(0xe6, 0x19, 0x4b),
+    Rgb(0x3c, 0xb4, 0x4b),
+    Rgb(0x43, 0x63, 0xd8),
+    Rgb(0xf5, 0x82, 0x31),
+    Rgb(0x91, 0x1e, 0xb4),
+    Rgb(0x42, 0xd4, 0xf4),
+    Rgb(0xf0, 0x32, 0xe6),
+    Rgb(0x80, 0x80, 0x00),
+];
 
-#[derive(Debug, Parser)]
+#[derive(Debug, Clone, Parser)]
 #[command(about)]
 struct Args {
     #[arg(short, long)]
@@ -30,163 +139,6777 @@ struct Args {
 
     #[arg(short, long, default_value = None)]
     relations: Option<Box<std::path::Path>>,
+
+    /// Color features by their `ele` tag, normalized over the rendered elevation range
+    #[arg(long)]
+    ele_color: bool,
+
+    /// Show a scale bar, measured at the middle latitude of the drawing
+    #[arg(long)]
+    scale_bar: bool,
+
+    /// Units of the scale bar
+    #[arg(long, value_enum, default_value_t = DistanceUnit::Km)]
+    scale_bar_units: DistanceUnit,
+
+    /// Embed this font file and set all text in it, so that labels look the same everywhere
+    #[arg(long)]
+    bundle_font: Option<Box<std::path::Path>>,
+
+    /// Credit the data source in the bottom right corner, by default as OpenStreetMap data
+    #[arg(long, num_args = 0..=1, default_missing_value = ATTRIBUTION)]
+    attribution: Option<String>,
+
+    /// Note the date of the data in the bottom left corner, by default the newest edit drawn
+    #[arg(long, num_args = 0..=1, value_name = "DATE")]
+    data_date: Option<Option<String>>,
+
+    /// Stroke each way with a gradient running from its start to its end
+    #[arg(long)]
+    gradient_along: bool,
+
+    /// Color of the start of ways in `--gradient-along` mode
+    #[arg(long, default_value = "#0000ff", value_parser = parse_color)]
+    gradient_start: Rgb,
+
+    /// Color of the end of ways in `--gradient-along` mode
+    #[arg(long, default_value = "#ff0000", value_parser = parse_color)]
+    gradient_end: Rgb,
+
+    /// Color ways by how much they turn, from blue where they run straight to red on their bends
+    #[arg(long, conflicts_with_all = ["gradient_along", "casing", "buffer"])]
+    curvature_color: bool,
+
+    /// Show a legend of the colors used by `--ele-color`
+    #[arg(long)]
+    legend: bool,
+
+    /// Color of the lowest elevation in `--ele-color` mode
+    #[arg(long, default_value = "#008000", value_parser = parse_color)]
+    ele_low: Rgb,
+
+    /// Color of the highest elevation in `--ele-color` mode
+    #[arg(long, default_value = "#ffffff", value_parser = parse_color)]
+    ele_high: Rgb,
+
+    /// Skip ways whose projected length is shorter than this
+    #[arg(long)]
+    min_length: Option<f64>,
+
+    /// Render every way and relation in the extract except the selected ones
+    #[arg(long)]
+    invert: bool,
+
+    /// Refuse to render more than this many selected ways and relations
+    #[arg(long)]
+    max_features: Option<usize>,
+
+    /// Also select ways with this tag, as `key=value`, or `key=a|b` to match any of the values
+    #[arg(long, value_parser = parse_tag_filter)]
+    way_tag: Vec<TagFilter>,
+
+    /// Also select relations with this tag, as `key=value`, or `key=a|b` to match any of the values
+    #[arg(long, value_parser = parse_tag_filter)]
+    rel_tag: Vec<TagFilter>,
+
+    /// Leave out ways and nodes with this tag, as `key=value` or `key=a|b`, even in selected relations
+    #[arg(long, value_parser = parse_tag_filter)]
+    exclude_tag: Vec<TagFilter>,
+
+    /// Also select features by the rules in this file, one like `relation type=route` or `way highway=primary` per line
+    #[arg(long)]
+    filter: Option<Box<std::path::Path>>,
+
+    /// Only select ways and relations last edited by this user
+    #[arg(long)]
+    user: Option<String>,
+
+    /// Only select ways and relations last edited in this changeset
+    #[arg(long)]
+    changeset: Option<i64>,
+
+    /// Emit relation members sorted by type and id, so output doesn't depend on member order
+    ///
+    /// Route relations keep their member order, since it encodes the route's sequence.
+    #[arg(long)]
+    deterministic: bool,
+
+    /// Emphasize these ways and relations (and their members) with a thicker, distinct stroke
+    #[arg(long, value_delimiter = ',')]
+    highlight: Vec<i64>,
+
+    /// Stroke color of highlighted features
+    #[arg(long, default_value = "#ff00ff", value_parser = parse_color)]
+    highlight_color: Rgb,
+
+    /// Color each way like the relation it belongs to, even when rendered on its own
+    ///
+    /// A way in several colored relations takes the color of the one with the lowest id.
+    #[arg(long)]
+    color_by_relation: bool,
+
+    /// Color the `forward` and `backward` members of routes in lighter shades of the route's color
+    #[arg(long)]
+    color_by_role: bool,
+
+    /// Color each relation by its id, unless it has a `colour` tag
+    #[arg(long)]
+    auto_color: bool,
+
+    /// Seed for `--auto-color`, to get a different palette
+    #[arg(long, default_value_t = 0)]
+    color_seed: u64,
+
+    /// Color each way from a fixed palette by its id, ignoring its tags, to tell ways apart
+    #[arg(long)]
+    debug_colors: bool,
+
+    /// Draw closed ways enclosing less than this area, in square projected units, as a dot
+    #[arg(long)]
+    collapse_small: Option<f64>,
+
+    /// Simplify ways at this tolerance, in projected units
+    ///
+    /// With `--simplify-algo visvalingam` the tolerance is an area, in square projected units.
+    #[arg(long)]
+    simplify: Option<f64>,
+
+    /// The algorithm to simplify ways with
+    #[arg(long, value_enum, default_value_t = SimplifyAlgo::DouglasPeucker)]
+    simplify_algo: SimplifyAlgo,
+
+    /// Simplify edges shared between ways once, so adjacent features stay joined
+    #[arg(long)]
+    simplify_preserve_topology: bool,
+
+    /// Keep nodes shared between ways when simplifying, so that networks stay connected
+    #[arg(long, conflicts_with = "simplify_preserve_topology")]
+    simplify_keep_shared: bool,
+
+    /// Don't simplify any way below this many points, lowering the tolerance for it instead
+    #[arg(long, conflicts_with = "simplify_preserve_topology")]
+    simplify_min_points: Option<usize>,
+
+    /// Write one SVG per zoom level, each simplified at its own tolerance, e.g. `10=20,14=2`
+    ///
+    /// The files are named after `--output` with the zoom level appended, e.g. `map-z14.svg`.
+    #[arg(long, value_delimiter = ',', value_parser = parse_lod, requires = "output")]
+    lod: Vec<(u32, f64)>,
+
+    /// Default stroke width of the document
+    #[arg(long, default_value_t = STROKE_WIDTH, value_parser = parse_non_negative)]
+    stroke_width: f64,
+
+    /// Units of `--stroke-width`: projected units, or screen pixels regardless of zoom
+    #[arg(long, value_enum, default_value_t = StrokeUnits::Projected)]
+    stroke_units: StrokeUnits,
+
+    /// Add points along segments of ways longer than this many degrees, so that they curve as projected
+    #[arg(long, value_parser = parse_positive, conflicts_with = "reproject_only")]
+    densify: Option<f64>,
+
+    /// Draw every feature in the same faint stroke, so that the more of them overlap, the darker it gets
+    #[arg(long)]
+    heatmap: bool,
+
+    /// Color closed ways by the direction they run in: red if clockwise, blue if counterclockwise
+    #[arg(long, conflicts_with_all = ["gradient_along", "curvature_color"])]
+    show_winding: bool,
+
+    /// Draw the convex hull of everything drawn behind the features, as its footprint
+    #[arg(long)]
+    hull: bool,
+
+    /// Draw an outline around each relation's members, filled translucently behind them
+    #[arg(long, value_enum)]
+    relation_outline: Option<OutlineShape>,
+
+    /// Half the widest gap between members that a concave outline spans, in meters
+    #[arg(long, default_value_t = 1000.0, value_parser = parse_positive)]
+    outline_radius: f64,
+
+    /// Draw latitude and longitude lines at this spacing in degrees behind the features
+    #[arg(long, value_parser = parse_positive)]
+    graticule: Option<f64>,
+
+    /// Make every feature drawn a link to its page on openstreetmap.org
+    #[arg(long)]
+    link_osm: bool,
+
+    /// Give ways and relations the value of this tag as their SVG id, like `ref`, instead of their OSM id
+    #[arg(long)]
+    id_from: Option<String>,
+
+    /// Group features into layers by the value of this tag
+    #[arg(long)]
+    layers_by: Option<String>,
+
+    /// Draw all the selected relations in one group with this id, where they'd otherwise be side by side
+    #[arg(long, conflicts_with_all = ["layers_by", "stream"])]
+    merge_group: Option<String>,
+
+    /// Write an SVG into DIR for each value of the tag KEY, all with the view box of the whole selection
+    #[arg(long, num_args = 2, value_names = ["KEY", "DIR"], conflicts_with_all = ["lod", "html", "stream", "reproject_only"])]
+    per_value: Vec<String>,
+
+    /// Write an SVG into DIR for each value of the tag KEY, each with a view box of its own,
+    /// and one named `untagged.svg` for features without the tag
+    #[arg(long, num_args = 2, value_names = ["KEY", "DIR"], conflicts_with_all = ["per_value", "lod", "html", "stream", "reproject_only"])]
+    split_by_tag: Vec<String>,
+
+    /// Write an HTML page containing the SVG, with controls to toggle `--layers-by` layers
+    #[arg(long)]
+    html: bool,
+
+    /// Indent the SVG for reading
+    #[arg(long, conflicts_with = "compact")]
+    pretty: bool,
+
+    /// Write the SVG without whitespace between elements
+    #[arg(long)]
+    compact: bool,
+
+    /// Write each feature out as soon as it is drawn, instead of building the whole SVG first
+    ///
+    /// This keeps large drawings from being held in memory,
+    /// at the cost of the options that need the whole drawing, like `--layers-by` or `--rotate`.
+    #[arg(long, conflicts_with_all = [
+        "lod", "html", "pretty", "compact", "layers_by", "endpoints", "graticule", "shadow",
+        "overview", "bbox", "bbox_from_relation", "crop_to_relation", "center_on_relation",
+        "rotate", "legend", "scale_bar", "attribution", "split_antimeridian", "reproject_only",
+        "bundle_font", "hull", "data_date",
+    ])]
+    stream: bool,
+
+    /// Resolution of PNG output, which is written when `--output` ends in `.png`
+    ///
+    /// The drawing is sized as if its SVG units were CSS pixels, 96 to the inch.
+    #[arg(long, default_value_t = 96.0, value_parser = parse_positive)]
+    dpi: f64,
+
+    /// Fill multipolygon and boundary relations and closed ways tagged as areas
+    ///
+    /// The ways making up a relation's rings are drawn as part of its filled shape,
+    /// and aren't drawn again on their own.
+    #[arg(long)]
+    fill: bool,
+
+    /// Draw ways with a `width` tag as filled outlines of that width, rather than as lines
+    #[arg(long)]
+    buffer: bool,
+
+    /// Close ways tagged as areas that don't end where they start, so they can be filled
+    #[arg(long)]
+    close_areas: bool,
+
+    /// How `--fill` fills closed ways that cross themselves
+    ///
+    /// Relations are always filled `evenodd`, so that their inner rings become holes.
+    #[arg(long, value_enum, default_value_t = FillRule::Nonzero)]
+    fill_rule: FillRule,
+
+    /// Draw the closed ways filled by `--fill` largest first, so that small ones aren't hidden
+    #[arg(long, requires = "fill")]
+    order_by_area: bool,
+
+    /// With `--fill`, still draw selected ways on their own when they're part of a filled relation
+    #[arg(long)]
+    draw_ring_members: bool,
+
+    /// Format of the output
+    #[arg(long, value_enum, default_value_t = OutputFormat::Svg, conflicts_with_all = ["lod", "html"])]
+    format: OutputFormat,
+
+    /// Only report this many missing nodes, ways and relations, then how many more there were
+    #[arg(long)]
+    max_warnings: Option<usize>,
+
+    /// Format of warnings on stderr: human-readable text, or one JSON object per line
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Only draw relation members of these kinds, e.g. `way,relation` to leave out stops
+    #[arg(long, value_enum, value_delimiter = ',')]
+    only_types: Vec<MemberType>,
+
+    /// Tags to copy onto relation groups as `data-` attributes, e.g. `type,name,network`
+    #[arg(long, value_delimiter = ',')]
+    group_attrs: Vec<String>,
+
+    /// Draw selections crossing the antimeridian continuously, split into a group for each side
+    #[arg(long)]
+    split_antimeridian: bool,
+
+    /// Draw ways with relative path commands, which are shorter for dense ways
+    #[arg(long)]
+    relative_paths: bool,
+
+    /// Show a miniature of everything selected in a corner, outlining the part in view
+    ///
+    /// Useful with `--bbox` and the like, to show where the drawing is.
+    #[arg(long)]
+    overview: bool,
+
+    /// Give the features a subtle drop shadow
+    #[arg(long)]
+    shadow: bool,
+
+    /// Leave out the `viewBox`, for consumers that place the projected coordinates themselves
+    #[arg(long)]
+    no_viewbox: bool,
+
+    /// The projection to draw in
+    #[arg(long, value_enum, default_value_t = MapProjection::WebMercator)]
+    projection: MapProjection,
+
+    /// Shift every projected x coordinate by this much, to line up with other drawings
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    offset_x: f64,
+
+    /// Shift every projected y coordinate by this much
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    offset_y: f64,
+
+    /// Rotate the drawing clockwise by this many degrees about its center
+    #[arg(long, allow_hyphen_values = true)]
+    rotate: Option<f64>,
+
+    /// Color the ways added, removed or modified since this older extract
+    #[arg(long)]
+    diff: Option<Box<std::path::Path>>,
+
+    /// Clip everything drawn to this box, as `min_lon,min_lat,max_lon,max_lat`
+    ///
+    /// Relations entirely outside the box are left out.
+    #[arg(long, value_parser = parse_bbox, conflicts_with = "split_antimeridian")]
+    bbox: Option<Bound>,
+
+    /// Clip everything drawn to the extent of this relation, as if it were passed to `--bbox`
+    #[arg(long, conflicts_with_all = ["bbox", "split_antimeridian"])]
+    bbox_from_relation: Option<i64>,
+
+    /// Clip everything drawn to the area of this multipolygon or boundary relation
+    ///
+    /// The relation itself is only drawn if it is selected, and then isn't clipped.
+    #[arg(long)]
+    crop_to_relation: Option<i64>,
+
+    /// Center the drawing on the middle of this relation's extent, keeping its size
+    #[arg(long)]
+    center_on_relation: Option<i64>,
+
+    /// Which members of a relation are drawn first, and so end up below the others
+    #[arg(long, value_enum, default_value_t = RenderOrder::Members)]
+    render_order: RenderOrder,
+
+    /// Draw the ways of route relations as dashes marching along them, animated in browsers
+    #[arg(long)]
+    flow_animate: bool,
+
+    /// Merge a relation's consecutive unfilled paths of the same style into one path each
+    #[arg(long, conflicts_with = "relative_paths")]
+    merge_paths: bool,
+
+    /// Draw member relations fainter the more deeply they are nested
+    #[arg(long)]
+    depth_fade: bool,
+
+    /// Label named relations with their name, in the middle of their extent
+    #[arg(long)]
+    relation_labels: bool,
+
+    /// Leave out relations with members missing from the extract, rather than drawing them partially
+    #[arg(long)]
+    require_complete: bool,
+
+    /// Mark the node members of relations, such as stops, with a dot
+    #[arg(long)]
+    node_markers: bool,
+
+    /// Mark node members tagged with a common `amenity`, `shop` or `tourism` with an icon for it
+    #[arg(long, requires = "node_markers")]
+    icons: bool,
+
+    /// Mark the ends of each way, larger where several ways end at the same node
+    #[arg(long)]
+    endpoints: bool,
+
+    /// Stroke ways as wide as their `width` tag, in meters
+    #[arg(long)]
+    width_tag: bool,
+
+    /// The narrowest `width` tag used by `--width-tag`, in meters; narrower ones are widened to it
+    #[arg(long, default_value_t = 0.0, value_parser = parse_non_negative)]
+    width_min: f64,
+
+    /// The widest `width` tag used by `--width-tag`, in meters; wider ones are narrowed to it
+    #[arg(long, default_value_t = 100.0, value_parser = parse_non_negative)]
+    width_max: f64,
+
+    /// Draw each way over a wider, darker casing, as is usual for roads
+    #[arg(long)]
+    casing: bool,
+
+    /// Instead of an SVG, write the projected coordinates of every node drawn, as CSV
+    #[arg(long, conflicts_with_all = ["lod", "html"])]
+    reproject_only: bool,
+
+    /// Instead of an SVG, list problems with the geometry of the selected ways and of the ways in the selected relations
+    #[arg(long, conflicts_with_all = ["lod", "html", "reproject_only"])]
+    validate_geometry: bool,
+
+    /// Show progress on stderr while reading and rendering
+    #[arg(long)]
+    progress: bool,
+
+    /// Treat the latitude of nodes as their longitude and vice versa, for data that mixes them up
+    #[arg(long)]
+    swap_latlon: bool,
+
+    /// Print rendering statistics to stderr
+    #[arg(long)]
+    stats: bool,
+
+    /// Write the WGS84 bounding box of the drawing to this file, as JSON `[minlon, minlat, maxlon, maxlat]`
+    #[arg(long)]
+    bounds_out: Option<Box<std::path::Path>>,
+
+    /// Warn when the drawing reaches further than this from the origin, which some viewers can't handle
+    #[arg(long, value_parser = parse_positive)]
+    max_coord: Option<f64>,
+
+    /// Instead of warning about `--max-coord`, move the drawing to be centered on the origin
+    #[arg(long, requires = "max_coord", conflicts_with = "stream")]
+    auto_fit_coords: bool,
+
+    /// Write a world file (like `.pgw` or `.wld`) placing the output in the projection's coordinates, for GIS
+    ///
+    /// Web Mercator coordinates are in meters and equirectangular ones in degrees;
+    /// an SVG is taken to have a pixel per unit.
+    #[arg(long, conflicts_with_all = ["lod", "stream", "rotate", "no_viewbox"])]
+    world_file: Option<Box<std::path::Path>>,
+
+    /// Write the tags of every way and relation drawn to this file, as CSV with a column per key
+    #[arg(long)]
+    tags_out: Option<Box<std::path::Path>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum SimplifyAlgo {
+    /// Drop points closer than the tolerance to the simplified line
+    DouglasPeucker,
+    /// Drop points forming triangles smaller than the tolerance with their neighbours
+    Visvalingam,
+}
+impl SimplifyAlgo {
+    /// Which points of a polyline this algorithm keeps.
+    fn mask(self, points: &[(f64, f64)], tolerance: f64) -> Vec<bool> {
+        match self {
+            SimplifyAlgo::DouglasPeucker => simplify_mask(points, tolerance),
+            SimplifyAlgo::Visvalingam => visvalingam_mask(points, tolerance),
+        }
+    }
+    fn simplify(self, points: Vec<(f64, f64)>, tolerance: f64) -> Vec<(f64, f64)> {
+        match self {
+            SimplifyAlgo::DouglasPeucker => simplify(points, tolerance),
+            SimplifyAlgo::Visvalingam => visvalingam(points, tolerance),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum FillRule {
+    /// Fill everything a way winds around
+    Nonzero,
+    /// Fill alternately, leaving out areas enclosed an even number of times
+    Evenodd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum StrokeUnits {
+    Projected,
+    Px,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum MapProjection {
+    /// Web Mercator (EPSG:3857), as used by web maps
+    WebMercator,
+    /// Latitude and longitude as they are, stretching areas away from the equator
+    Equirectangular,
+}
+impl MapProjection {
+    fn projection(self) -> Box<dyn Projection> {
+        match self {
+            MapProjection::WebMercator => Box::new(WebMercator),
+            MapProjection::Equirectangular => Box::new(Equirectangular),
+        }
+    }
+    /// How many meters or degrees of the projection's usual coordinates a projected unit is.
+    fn world_units(self) -> f64 {
+        match self {
+            MapProjection::WebMercator => WEB_MERCATOR_RADIUS / SCALE,
+            MapProjection::Equirectangular => 180.0 / PI / SCALE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Svg,
+    /// A TopoJSON topology in WGS84, with edges shared between ways stored once
+    Topojson,
+    /// A line of WKT in WGS84 per feature, after its kind and id, e.g. `way/10`
+    Wkt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum MemberType {
+    Node,
+    Way,
+    Relation,
+}
+impl MemberType {
+    fn of(id: OsmId) -> Self {
+        match id {
+            OsmId::Node(_) => MemberType::Node,
+            OsmId::Way(_) => MemberType::Way,
+            OsmId::Relation(_) => MemberType::Relation,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum DistanceUnit {
+    /// Kilometers
+    Km,
+    /// Miles
+    Mi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum RenderOrder {
+    /// In the order they are in the relation
+    Members,
+    /// Ways and nodes before member relations
+    WaysFirst,
+    /// Member relations before ways and nodes
+    RelationsFirst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum OutlineShape {
+    /// The convex hull of the members
+    Convex,
+    /// An alpha shape hugging the members, set apart where they are
+    Concave,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+/// A progress bar on stderr, or a hidden one if progress isn't wanted.
+fn progress_bar(enabled: bool, len: u64, template: &str) -> ProgressBar {
+    if !enabled {
+        return ProgressBar::hidden();
+    }
+    ProgressBar::new(len).with_style(ProgressStyle::with_template(template).unwrap())
+}
+
+/// Reads an extract, showing how far through it each pass of reading it has got.
+///
+/// Reading it again starts the bar over, rather than running it past the extract's length.
+struct ReadProgress<R> {
+    inner: R,
+    progress: ProgressBar,
+}
+
+impl<R: Read> Read for ReadProgress<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.progress.inc(read as u64);
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for ReadProgress<R> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let pos = self.inner.seek(pos)?;
+        if pos == 0 {
+            self.progress.reset();
+        } else {
+            self.progress.set_position(pos);
+        }
+        Ok(pos)
+    }
+}
+
+fn parse_non_negative(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v >= 0.0 => Ok(v),
+        Ok(v) => Err(format!("must not be negative, got {v}")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_positive(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v > 0.0 => Ok(v),
+        Ok(v) => Err(format!("must be positive, got {v}")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_bbox(s: &str) -> Result<Bound, String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let &[min_lon, min_lat, max_lon, max_lat] = values.as_slice() else {
+        return Err(format!(
+            "expected min_lon,min_lat,max_lon,max_lat, got {s:?}"
+        ));
+    };
+    if min_lon > max_lon || min_lat > max_lat {
+        return Err(format!("minimum is more than maximum in {s:?}"));
+    }
+    Ok(Bound {
+        lat: min_lat..max_lat,
+        lon: min_lon..max_lon,
+        lon_east: min_lon..max_lon,
+    })
+}
+
+/// A tag to select features by, matching any of several values.
+#[derive(Debug, Clone, PartialEq)]
+struct TagFilter {
+    key: String,
+    values: Vec<String>,
+}
+impl TagFilter {
+    fn matches(&self, tags: &Tags) -> bool {
+        tags.get(self.key.as_str())
+            .is_some_and(|v| self.values.iter().any(|value| value == v))
+    }
+}
+
+fn parse_tag_filter(s: &str) -> Result<TagFilter, String> {
+    let (key, values) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected key=value, got {s:?}"))?;
+    Ok(TagFilter {
+        key: key.to_string(),
+        values: values.split('|').map(str::to_string).collect(),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rgb(u8, u8, u8);
+impl Rgb {
+    fn lerp(self, other: Rgb, t: f64) -> Rgb {
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Rgb(
+            mix(self.0, other.0),
+            mix(self.1, other.1),
+            mix(self.2, other.2),
+        )
+    }
+}
+impl std::fmt::Display for Rgb {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+fn parse_color(s: &str) -> Result<Rgb, String> {
+    let hex = s
+        .strip_prefix('#')
+        .filter(|h| h.len() == 6)
+        .ok_or_else(|| format!("expected a color like #rrggbb, got {s:?}"))?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string());
+    Ok(Rgb(channel(0)?, channel(2)?, channel(4)?))
 }
 
-#[derive(Debug, PartialEq)]
+fn parse_lod(s: &str) -> Result<(u32, f64), String> {
+    let (zoom, tolerance) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected zoom=tolerance, got {s:?}"))?;
+    Ok((
+        zoom.parse()
+            .map_err(|e| format!("bad zoom {zoom:?}: {e}"))?,
+        tolerance
+            .parse()
+            .map_err(|e| format!("bad tolerance {tolerance:?}: {e}"))?,
+    ))
+}
+
+fn lod_path(output: &std::path::Path, zoom: u32) -> std::path::PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output
+        .extension()
+        .unwrap_or("svg".as_ref())
+        .to_string_lossy();
+    output.with_file_name(format!("{stem}-z{zoom}.{extension}"))
+}
+
+#[derive(Debug, Clone, PartialEq)]
 struct Bound {
     lat: Range<f64>,
     lon: Range<f64>,
+    /// Longitudes shifted into `0..360`, to find extents crossing the antimeridian.
+    lon_east: Range<f64>,
 }
 impl Bound {
     fn new() -> Self {
         Bound {
             lat: 0f64..0f64,
             lon: 0f64..0f64,
+            lon_east: 0f64..0f64,
         }
     }
     fn is_empty(&self) -> bool {
         *self == Self::new()
     }
     fn update(&mut self, node: &Node) {
+        let east = node.lon().rem_euclid(360.0);
         *self = if self.is_empty() {
             Bound {
                 lat: node.lat()..node.lat(),
                 lon: node.lon()..node.lon(),
+                lon_east: east..east,
             }
         } else {
             Bound {
                 lat: self.lat.start.min(node.lat())..self.lat.end.max(node.lat()),
                 lon: self.lon.start.min(node.lon())..self.lon.end.max(node.lon()),
+                lon_east: self.lon_east.start.min(east)..self.lon_east.end.max(east),
             }
         }
     }
+    /// Grows the bound to include another.
+    fn extend(&mut self, other: &Bound) {
+        if other.is_empty() {
+            return;
+        }
+        if self.is_empty() {
+            *self = other.clone();
+        } else {
+            *self = Bound {
+                lat: self.lat.start.min(other.lat.start)..self.lat.end.max(other.lat.end),
+                lon: self.lon.start.min(other.lon.start)..self.lon.end.max(other.lon.end),
+                lon_east: self.lon_east.start.min(other.lon_east.start)
+                    ..self.lon_east.end.max(other.lon_east.end),
+            };
+        }
+    }
+    /// Whether the bound overlaps another, ignoring the antimeridian.
+    fn intersects(&self, other: &Bound) -> bool {
+        self.lat.start <= other.lat.end
+            && other.lat.start <= self.lat.end
+            && self.lon.start <= other.lon.end
+            && other.lon.start <= self.lon.end
+    }
+    /// The part of the bound inside another, ignoring the antimeridian.
+    fn intersection(&self, other: &Bound) -> Bound {
+        if !self.intersects(other) {
+            return Bound::new();
+        }
+        let lon = self.lon.start.max(other.lon.start)..self.lon.end.min(other.lon.end);
+        Bound {
+            lat: self.lat.start.max(other.lat.start)..self.lat.end.min(other.lat.end),
+            lon_east: lon.clone(),
+            lon,
+        }
+    }
+    /// Whether the bound is narrower when taken across the antimeridian.
+    fn crosses_antimeridian(&self) -> bool {
+        self.lon_east.end - self.lon_east.start < self.lon.end - self.lon.start
+    }
+    /// The bound with longitudes west of the antimeridian continued past 180.
+    fn unwrapped(&self) -> Bound {
+        Bound {
+            lon: self.lon_east.clone(),
+            ..self.clone()
+        }
+    }
+    /// The WGS84 bounding box as `(minlon, minlat, maxlon, maxlat)`.
+    ///
+    /// When the narrowest box crosses the antimeridian, `minlon` is greater than `maxlon`.
+    fn wgs84(&self) -> (f64, f64, f64, f64) {
+        let lon = if self.crosses_antimeridian() {
+            let west = |l: f64| if l > 180.0 { l - 360.0 } else { l };
+            west(self.lon_east.start)..west(self.lon_east.end)
+        } else {
+            self.lon.clone()
+        };
+        (lon.start, self.lat.start, lon.end, self.lat.end)
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
-    let ways = args.ways.map_or_else(
+    let mut args = Args::parse();
+    if args.width_min > args.width_max {
+        return Err("--width-min must not be more than --width-max".into());
+    }
+    let ways = args.ways.as_deref().map_or_else(
         || Ok(BTreeSet::new()),
-        |w| {
-            BufReader::new(File::open(w)?)
-                .lines()
-                .map(|l| -> Result<_, Box<dyn Error>> { Ok(WayId(l?.parse()?)) })
-                .collect()
-        },
+        |w| read_ids(w, WayId).map_err(|e| in_file(w, e)),
     )?;
-    let relations = args.relations.map_or_else(
+    let relations = args.relations.as_deref().map_or_else(
         || Ok(BTreeSet::new()),
-        |w| {
-            BufReader::new(File::open(w)?)
-                .lines()
-                .map(|l| -> Result<_, Box<dyn Error>> { Ok(RelationId(l?.parse()?)) })
-                .collect()
-        },
+        |r| read_ids(r, RelationId).map_err(|e| in_file(r, e)),
     )?;
 
-    let objs = OsmPbfReader::new(File::open(args.data)?).get_objs_and_deps(|o| match o {
-        osmpbfreader::OsmObj::Node(_) => false,
-        osmpbfreader::OsmObj::Way(way) => ways.contains(&way.id),
-        osmpbfreader::OsmObj::Relation(relation) => relations.contains(&relation.id),
+    if let Some(path) = &args.filter {
+        let (way_filters, relation_filters) = read_filter(path, args.log_format)?;
+        args.way_tag.extend(way_filters);
+        args.rel_tag.extend(relation_filters);
+    }
+    let Selection {
+        mut objs,
+        mut ways,
+        relations,
+        edit_times,
+    } = select(&mut args, ways, relations).map_err(|e| match e {
+        // Only `--bbox-from-relation` asks for an object by id that it can't do without.
+        RenderError::MissingReference { .. } | RenderError::NothingToDraw { .. } => {
+            format!("--bbox-from-relation: {e}")
+        }
+        e => e.to_string(),
     })?;
+    let changes = match &args.diff {
+        Some(path) => diff(path, &mut objs, &mut ways, &relations)?,
+        None => BTreeMap::new(),
+    };
 
-    let mut bound = Bound::new();
-    let mut svg = Document::new()
-        .set("stroke", "#000000")
-        .set("stroke-width", 0.0000035 * SCALE)
-        .set("stroke-linecap", "round")
-        .set("stroke-linejoin", "round");
-
-    for rel in relations {
-        if let Some(rel) = objs.get(&OsmId::Relation(rel)) {
-            svg = svg.add(relation_to_group(
-                &objs,
-                &mut bound,
-                rel.relation().unwrap(),
-            ));
-        } else {
-            eprintln!("relation {} not found", rel.0);
+    check_feature_count(args.max_features, relations.len() + ways.len())?;
+
+    if args.format == OutputFormat::Topojson {
+        let topology = topojson(&objs, &objs, &relations, &ways).to_string();
+        match args.output.as_deref() {
+            Some(path) => write_atomically(path, (topology + "\n").as_bytes())?,
+            None => println!("{topology}"),
         }
+        return Ok(());
     }
-    for way in ways {
-        if let Some(way) = objs.get(&OsmId::Way(way)) {
-            svg = svg.add(way_to_path(&objs, &mut bound, way.way().unwrap()));
-        } else {
-            eprintln!("way {} not found", way.0);
+    if args.validate_geometry {
+        let report = validate_geometry(&objs, &relations, &ways);
+        match args.output.as_deref() {
+            Some(path) => write_atomically(path, report.as_bytes())?,
+            None => print!("{report}"),
+        }
+        return Ok(());
+    }
+    if args.format == OutputFormat::Wkt {
+        let lines = relations
+            .iter()
+            .map(|r| OsmId::Relation(*r))
+            .chain(ways.iter().map(|w| OsmId::Way(*w)))
+            .filter_map(|id| {
+                Some(format!(
+                    "{}/{}\t{}\n",
+                    kind(id),
+                    id.inner_id(),
+                    wkt(&objs, &objs, id)?
+                ))
+            })
+            .collect::<String>();
+        match args.output.as_deref() {
+            Some(path) => write_atomically(path, lines.as_bytes())?,
+            None => print!("{lines}"),
         }
+        return Ok(());
     }
 
-    if !bound.is_empty() {
-        let upper_left = project(bound.lat.end.to_radians(), bound.lon.start.to_radians());
-        let lower_right = project(bound.lat.start.to_radians(), bound.lon.end.to_radians());
-        svg = svg.set(
-            "viewBox",
-            (
-                upper_left.0,
-                upper_left.1,
-                lower_right.0 - upper_left.0,
-                lower_right.1 - upper_left.1,
-            ),
-        );
+    let font = args.bundle_font.as_deref().map(font_style).transpose()?;
+    if !args.per_value.is_empty() || !args.split_by_tag.is_empty() {
+        let font = font.as_ref();
+        return save_by_value(&args, &objs, &changes, &edit_times, &relations, &ways, font);
     }
-    if let Some(output) = args.output {
-        svg::save(output, &svg)?;
+    if args.lod.is_empty() {
+        // Moving the drawing to fit `--max-coord` takes drawing it again with other offsets.
+        let fitted;
+        let mut renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, args.simplify);
+        let svg = if args.stream {
+            match args.output.as_deref() {
+                Some(path) if path.extension().is_some_and(|e| e == "png") => {
+                    return Err("--stream can't write PNG output".into());
+                }
+                Some(path) => {
+                    write_atomically_with(path, |file| renderer.stream(&relations, &ways, file))?
+                }
+                None => {
+                    let mut out = std::io::BufWriter::new(stdout().lock());
+                    renderer.stream(&relations, &ways, &mut out)?;
+                    out.flush()?;
+                }
+            }
+            None
+        } else {
+            let mut svg = renderer.render(&relations, &ways);
+            let too_far = view_box(&svg)
+                .zip(args.max_coord)
+                .filter(|(v, max)| reach(*v) > *max);
+            if let Some(([x, y, width, height], max)) = too_far {
+                if args.auto_fit_coords {
+                    fitted = Args {
+                        offset_x: args.offset_x - (x + width / 2.0),
+                        offset_y: args.offset_y - (y + height / 2.0),
+                        ..args.clone()
+                    };
+                    renderer =
+                        Renderer::new(&objs, &objs, &changes, &edit_times, &fitted, args.simplify);
+                    svg = renderer.render(&relations, &ways);
+                }
+                match view_box(&svg).map(reach).filter(|r| *r > max) {
+                    Some(reach) if args.auto_fit_coords => {
+                        let message = format!(
+                            "the drawing reaches {reach} from the origin even centered on it, beyond --max-coord {max}; try a smaller selection"
+                        );
+                        warn(args.log_format, "coordinates_too_large", 0, message);
+                    }
+                    Some(reach) => {
+                        let message = format!(
+                            "the drawing reaches {reach} from the origin, beyond --max-coord {max}; try --auto-fit-coords or --offset-x and --offset-y"
+                        );
+                        warn(args.log_format, "coordinates_too_large", 0, message);
+                    }
+                    None => {}
+                }
+            }
+            if let Some(style) = &font {
+                svg = svg.add(style.clone());
+            }
+            Some(svg)
+        };
+        if args.stats {
+            renderer.stats.print();
+        }
+        if let Some(path) = &args.bounds_out {
+            save_bounds(path, renderer.stats.bounds)?;
+        }
+        if let Some(path) = &args.tags_out {
+            save_tags(path, &objs, &renderer.rendered)?;
+        }
+        if let (Some(path), Some(svg)) = (&args.world_file, &svg) {
+            save_world_file(path, renderer.args, svg)?;
+        }
+        if args.reproject_only {
+            save_projected(args.output.as_deref(), &renderer.projected)?;
+        } else if let Some(svg) = &svg {
+            save(&args, args.output.as_deref(), svg, &renderer.layers)?;
+        }
     } else {
-        svg::write(stdout(), &svg)?;
+        let output = args.output.as_ref().unwrap();
+        for &(zoom, tolerance) in &args.lod {
+            let mut renderer =
+                Renderer::new(&objs, &objs, &changes, &edit_times, &args, Some(tolerance));
+            let mut svg = renderer.render(&relations, &ways);
+            if let Some(style) = &font {
+                svg = svg.add(style.clone());
+            }
+            if args.stats {
+                eprintln!("zoom {zoom}:");
+                renderer.stats.print();
+            }
+            // Simplification doesn't move any nodes, so every level has the same bounds.
+            if let Some(path) = &args.bounds_out {
+                save_bounds(path, renderer.stats.bounds)?;
+            }
+            if let Some(path) = &args.tags_out {
+                save_tags(path, &objs, &renderer.rendered)?;
+            }
+            save(&args, Some(&lod_path(output, zoom)), &svg, &renderer.layers)?;
+        }
     }
 
     Ok(())
 }
 
-fn relation_to_group(objs: &BTreeMap<OsmId, OsmObj>, bound: &mut Bound, rel: &Relation) -> Group {
-    let mut group = set_stroke(Group::new(), &rel.tags).set("id", rel.id.0);
-    for r in &rel.refs {
-        if let Some(r) = objs.get(&r.member) {
-            match r {
-                OsmObj::Way(way) => group = group.add(way_to_path(objs, bound, way)),
-                OsmObj::Relation(rel) => group = group.add(relation_to_group(objs, bound, rel)),
-                OsmObj::Node(_) => {}
-            }
-        } else {
-            eprintln!("ref {:?} of relation {} not found", r.member, rel.id.0)
+/// A line for each problem with the geometry of the selected ways and those in the selected
+/// relations, after the id of the way, e.g. `way/10`.
+fn validate_geometry(
+    objs: &BTreeMap<OsmId, OsmObj>,
+    relations: &BTreeSet<RelationId>,
+    ways: &BTreeSet<WayId>,
+) -> String {
+    let mut checked = ways.clone();
+    let mut stack = relations.iter().copied().collect::<Vec<_>>();
+    let mut seen = BTreeSet::new();
+    while let Some(id) = stack.pop() {
+        if !seen.insert(id) {
+            continue;
         }
+        let Some(rel) = objs.get(&OsmId::Relation(id)).and_then(OsmObj::relation) else {
+            continue;
+        };
+        checked.extend(rel.refs.iter().filter_map(|r| r.member.way()));
+        stack.extend(rel.refs.iter().filter_map(|r| r.member.relation()));
     }
-    group
-}
 
-fn way_to_path(objs: &BTreeMap<OsmId, OsmObj>, bound: &mut Bound, way: &Way) -> Path {
-    let mut data = Data::new();
-    let mut first = true;
-    for n in &way.nodes {
-        if let Some(n) = objs.get(&OsmId::Node(*n)) {
-            let n = n.node().unwrap();
-            bound.update(n);
-            if first {
-                data = data.move_to(project_node(n));
-                first = false;
-            } else {
-                data = data.line_to(project_node(n));
+    let mut report = String::new();
+    for id in checked {
+        let Some(way) = objs.get(&OsmId::Way(id)).and_then(OsmObj::way) else {
+            continue;
+        };
+        let mut problems = Vec::new();
+        if way.nodes.len() < 2 {
+            problems.push("has fewer than 2 nodes".to_string());
+        }
+        let lat_lon = |n: NodeId| objs.lat_lon(n);
+        for pair in way.nodes.windows(2) {
+            if pair[0] == pair[1] {
+                problems.push(format!("repeats node {}", pair[0].0));
+            } else if lat_lon(pair[0]).is_some() && lat_lon(pair[0]) == lat_lon(pair[1]) {
+                problems.push(format!(
+                    "has a zero-length segment between nodes {} and {}",
+                    pair[0].0, pair[1].0
+                ));
             }
-        } else {
-            eprintln!("node {} not found", n.0);
+        }
+        if way.is_open() && is_area(&way.tags) && way.nodes.len() > 1 {
+            problems.push("is tagged as an area but not closed".to_string());
+        }
+        if way.is_closed() {
+            let ring = way
+                .nodes
+                .iter()
+                .filter_map(|n| lat_lon(*n))
+                .map(|(lat, lon)| (lon, lat))
+                .collect::<Vec<_>>();
+            if self_intersects(&ring) {
+                problems.push("crosses itself".to_string());
+            }
+        }
+        for problem in problems {
+            report.push_str(&format!("way/{}\t{problem}\n", id.0));
         }
     }
-    set_stroke(Path::new(), &way.tags)
-        .set("id", way.id.0)
-        .set("fill", "none")
-        .set("d", data)
+    report
 }
 
-fn set_stroke<N: svg::Node>(mut node: N, tags: &Tags) -> N {
-    if let Some(color) = tags.get("colour").filter(|s| s.starts_with('#')) {
-        node.assign("stroke", color.to_string())
-    };
-    node
+/// Writes the bounding box as a JSON array, or `null` if nothing was drawn.
+fn save_bounds(
+    path: &std::path::Path,
+    bounds: Option<(f64, f64, f64, f64)>,
+) -> Result<(), Box<dyn Error>> {
+    let json = bounds
+        .map(|(min_lon, min_lat, max_lon, max_lat)| json!([min_lon, min_lat, max_lon, max_lat]));
+    let contents = format!("{}\n", serde_json::Value::from(json));
+    write_atomically(path, contents.as_bytes())?;
+    Ok(())
 }
 
-fn project_node(node: &Node) -> (f64, f64) {
-    project(node.lat().to_radians(), node.lon().to_radians())
+/// The view box of a drawing, as `[x, y, width, height]`.
+fn view_box(svg: &Document) -> Option<[f64; 4]> {
+    let numbers = svg::Node::get_attributes(svg)?
+        .get("viewBox")?
+        .split_whitespace()
+        .map(|n| n.parse().ok())
+        .collect::<Option<Vec<f64>>>()?;
+    numbers.try_into().ok()
 }
 
-fn project(lat: f64, lon: f64) -> (f64, f64) {
-    (lon * SCALE, (-lat / 2.0 + PI / 4.0).tan().ln() * SCALE)
+/// How far from the origin the view box of a drawing reaches, in either direction.
+fn reach([x, y, width, height]: [f64; 4]) -> f64 {
+    [x, y, x + width, y + height]
+        .into_iter()
+        .fold(0.0, |reach, v| reach.max(v.abs()))
+}
+
+/// Writes the world file of the drawing: the size of a pixel and where the upper left one is.
+fn save_world_file(
+    path: &std::path::Path,
+    args: &Args,
+    svg: &Document,
+) -> Result<(), Box<dyn Error>> {
+    let view_box = view_box(svg).ok_or("--world-file needs something to be drawn")?;
+    let png = args
+        .output
+        .as_deref()
+        .is_some_and(|p| p.extension().is_some_and(|e| e == "png"));
+    // PNGs are drawn at `--dpi` pixels per 96 units.
+    let pixel = if png { 96.0 / args.dpi } else { 1.0 };
+    let k = args.projection.world_units();
+    // The world file places the middle of the pixel, without the offsets and with y growing northwards.
+    let x = view_box[0] + pixel / 2.0 - args.offset_x;
+    let y = view_box[1] + pixel / 2.0 - args.offset_y;
+    let lines = [pixel * k, 0.0, 0.0, -pixel * k, x * k, -y * k];
+    let contents = lines.iter().map(|v| format!("{v}\n")).collect::<String>();
+    write_atomically(path, contents.as_bytes())?;
+    Ok(())
+}
+
+/// Writes a CSV table of the tags of the given features, with a column for every key.
+fn save_tags(
+    path: &std::path::Path,
+    objs: &BTreeMap<OsmId, OsmObj>,
+    ids: &BTreeSet<OsmId>,
+) -> Result<(), Box<dyn Error>> {
+    let keys = ids
+        .iter()
+        .flat_map(|id| objs[id].tags().keys())
+        .map(|k| k.as_str())
+        .collect::<BTreeSet<_>>();
+    write_atomically_with(path, |csv| {
+        // Prefixed so as not to clash with the `type` key of relations.
+        let header = ["osm_type", "osm_id"]
+            .into_iter()
+            .chain(keys.iter().copied());
+        writeln!(
+            csv,
+            "{}",
+            header.map(csv_field).collect::<Vec<_>>().join(",")
+        )?;
+        for id in ids {
+            let tags = objs[id].tags();
+            let id_string = id.inner_id().to_string();
+            let row = [kind(*id), id_string.as_str()]
+                .into_iter()
+                .chain(keys.iter().map(|&k| tags.get(k).map_or("", |v| v.as_str())));
+            writeln!(csv, "{}", row.map(csv_field).collect::<Vec<_>>().join(","))?;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Writes the projected coordinates of nodes as CSV to `path`, or stdout if there is none.
+fn save_projected(
+    path: Option<&std::path::Path>,
+    projected: &BTreeMap<NodeId, (f64, f64)>,
+) -> Result<(), Box<dyn Error>> {
+    let write = |csv: &mut dyn Write| {
+        writeln!(csv, "osm_node_id,x,y")?;
+        for (id, (x, y)) in projected {
+            writeln!(csv, "{},{x},{y}", id.0)?;
+        }
+        Ok(())
+    };
+    match path {
+        Some(path) => write_atomically_with(path, write)?,
+        None => {
+            let mut out = stdout().lock();
+            write(&mut out)?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field if it needs it.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Writes an SVG for each value of the `--per-value` or `--split-by-tag` key into its directory.
+fn save_by_value(
+    args: &Args,
+    objs: &BTreeMap<OsmId, OsmObj>,
+    changes: &BTreeMap<WayId, Change>,
+    edit_times: &BTreeMap<OsmId, i64>,
+    relations: &BTreeSet<RelationId>,
+    ways: &BTreeSet<WayId>,
+    font: Option<&Style>,
+) -> Result<(), Box<dyn Error>> {
+    let shared_view_box = !args.per_value.is_empty();
+    let split = if shared_view_box {
+        &args.per_value
+    } else {
+        &args.split_by_tag
+    };
+    let [key, dir] = &split[..] else {
+        unreachable!("both take a key and a directory");
+    };
+    // Drawing everything first gives the view box that every value's drawing shares.
+    let view_box = shared_view_box
+        .then(|| {
+            let full = Renderer::new(objs, objs, changes, edit_times, args, args.simplify)
+                .render(relations, ways);
+            svg::Node::get_attributes(&full).and_then(|a| a.get("viewBox").cloned())
+        })
+        .flatten();
+    let value = |id: OsmId| {
+        objs.get(&id)
+            .and_then(|o| o.tags().get(key.as_str()))
+            .map(|v| v.as_str())
+    };
+    // Features without the tag are only drawn by `--split-by-tag`, under `None`.
+    let mut values = BTreeMap::<Option<&str>, (BTreeSet<RelationId>, BTreeSet<WayId>)>::new();
+    for r in relations {
+        let value = value(OsmId::Relation(*r));
+        if value.is_some() || !shared_view_box {
+            values.entry(value).or_default().0.insert(*r);
+        }
+    }
+    for w in ways {
+        let value = value(OsmId::Way(*w));
+        if value.is_some() || !shared_view_box {
+            values.entry(value).or_default().1.insert(*w);
+        }
+    }
+    std::fs::create_dir_all(dir)?;
+    // Values like `a b` and `a_b` make the same name, so later ones get an OSM id too.
+    let mut names = BTreeSet::new();
+    for (value, (relations, ways)) in &values {
+        let mut renderer = Renderer::new(objs, objs, changes, edit_times, args, args.simplify);
+        let mut svg = renderer.render(relations, ways);
+        if let Some(view_box) = &view_box {
+            svg = svg.set("viewBox", view_box.clone());
+        }
+        if let Some(style) = font {
+            svg = svg.add(style.clone());
+        }
+        let first = relations
+            .first()
+            .map(|r| r.0)
+            .or_else(|| ways.first().map(|w| w.0))
+            .unwrap_or_default();
+        let name = unique_id(
+            &mut names,
+            value.map_or("untagged".to_string(), layer_id),
+            first,
+        );
+        let path = std::path::Path::new(dir).join(format!("{name}.svg"));
+        save(args, Some(&path), &svg, &renderer.layers)?;
+    }
+    Ok(())
+}
+
+/// Writes the document to `path`, or stdout if there is none.
+fn save(
+    args: &Args,
+    path: Option<&std::path::Path>,
+    svg: &Document,
+    layers: &[(String, String)],
+) -> Result<(), Box<dyn Error>> {
+    match (path, args.html) {
+        (Some(path), false) if path.extension().is_some_and(|e| e == "png") => {
+            write_atomically(path, &png(svg, args.dpi)?)?
+        }
+        (Some(path), false) => write_atomically(path, svg_string(args, svg).as_bytes())?,
+        (None, false) => stdout().write_all(svg_string(args, svg).as_bytes())?,
+        (Some(path), true) => {
+            write_atomically(path, html(svg_string(args, svg), layers).as_bytes())?
+        }
+        (None, true) => stdout().write_all(html(svg_string(args, svg), layers).as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Writes a file through a temporary file next to it, so that it is replaced all at once.
+fn write_atomically(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    write_atomically_with(path, |out| out.write_all(contents))
+}
+
+/// Like [`write_atomically`], for contents written out a piece at a time.
+fn write_atomically_with(
+    path: &std::path::Path,
+    write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+    let result = File::create(&temp)
+        .and_then(|file| {
+            let mut out = std::io::BufWriter::new(file);
+            write(&mut out)?;
+            out.flush()
+        })
+        .and_then(|()| std::fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Serializes the SVG, indented with `--pretty` or with no whitespace between elements with `--compact`.
+fn svg_string(args: &Args, svg: &Document) -> String {
+    if !args.pretty && !args.compact {
+        return svg.to_string();
+    }
+    let mut out = String::new();
+    write_node(svg, args.pretty.then_some(0), &mut out);
+    out
+}
+
+/// Serializes a node, on its own indented lines if there is a `depth` to indent it to.
+fn write_node(node: &dyn svg::Node, depth: Option<usize>, out: &mut String) {
+    // Only elements have attributes; anything else, like text, is written as is.
+    let Some(attributes) = node.get_attributes() else {
+        out.push_str(&node.to_string());
+        return;
+    };
+    if let Some(depth) = depth {
+        out.push_str(&"  ".repeat(depth));
+    }
+    out.push('<');
+    out.push_str(node.get_name());
+    let mut attributes = attributes.iter().collect::<Vec<_>>();
+    attributes.sort_by_key(|(name, _)| name.as_str());
+    for (name, value) in attributes {
+        out.push_str(&format!(" {name}=\"{}\"", escape_html(value)));
+    }
+    let children = node.get_children().map_or(&[][..], |c| &c[..]);
+    if children.is_empty() {
+        out.push_str("/>");
+    } else {
+        out.push('>');
+        // Whitespace next to text would change it, so elements containing text stay on one line.
+        let inline = children.iter().any(|c| c.get_attributes().is_none());
+        if !inline && depth.is_some() {
+            out.push('\n');
+        }
+        for child in children {
+            write_node(&**child, depth.filter(|_| !inline).map(|d| d + 1), out);
+        }
+        if let (false, Some(depth)) = (inline, depth) {
+            out.push_str(&"  ".repeat(depth));
+        }
+        out.push_str(&format!("</{}>", node.get_name()));
+    }
+    if depth.is_some() {
+        out.push('\n');
+    }
+}
+
+/// Rasterizes the SVG, at `dpi` pixels per 96 SVG units.
+fn png(svg: &Document, dpi: f64) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let tree = usvg::Tree::from_str(&svg.to_string(), &options)?;
+    let scale = (dpi / 96.0) as f32;
+    let size = tree
+        .size()
+        .to_int_size()
+        .scale_by(scale)
+        .ok_or("image size out of range")?;
+    let mut pixmap =
+        tiny_skia::Pixmap::new(size.width(), size.height()).ok_or("image size out of range")?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    Ok(pixmap.encode_png()?)
+}
+
+/// An HTML page showing the SVG, with a checkbox per layer to show or hide it.
+fn html(svg: String, layers: &[(String, String)]) -> String {
+    let mut page = String::from(concat!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+        "<style>svg { width: 100%; height: 100vh; }</style>\n</head>\n<body>\n",
+    ));
+    if !layers.is_empty() {
+        page.push_str("<fieldset id=\"layers\">\n<legend>Layers</legend>\n");
+        for (value, id) in layers {
+            page.push_str(&format!(
+                concat!(
+                    "<label><input type=\"checkbox\" checked onchange=\"",
+                    "document.getElementById('{}').style.display = this.checked ? '' : 'none'",
+                    "\"> {}</label>\n",
+                ),
+                id,
+                escape_html(value),
+            ));
+        }
+        page.push_str("</fieldset>\n");
+    }
+    page.push_str(&svg);
+    page.push_str("\n</body>\n</html>\n");
+    page
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The name of the `data-` attribute carrying a tag, e.g. `data-name-en` for `name:en`.
+fn data_attribute(key: &str) -> String {
+    let key = key.chars().map(|c| {
+        if c.is_ascii_alphanumeric() {
+            c.to_ascii_lowercase()
+        } else {
+            '-'
+        }
+    });
+    "data-".chars().chain(key).collect()
+}
+
+/// The SVG id of the layer for a tag value.
+fn layer_id(value: &str) -> String {
+    let value = value.chars().map(|c| {
+        if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+            c
+        } else {
+            '_'
+        }
+    });
+    "layer-".chars().chain(value).collect()
+}
+
+/// `id`, or if it's already `taken`, `id` followed by `suffix`, and then a count if need be.
+///
+/// The id returned is taken from then on.
+fn unique_id(taken: &mut BTreeSet<String>, id: String, suffix: impl std::fmt::Display) -> String {
+    let mut unique = id.clone();
+    let mut count = 1;
+    while taken.contains(&unique) {
+        unique = match count {
+            1 => format!("{id}-{suffix}"),
+            _ => format!("{id}-{suffix}-{count}"),
+        };
+        count += 1;
+    }
+    taken.insert(unique.clone());
+    unique
+}
+
+/// A tag value made into a valid SVG id, replacing what ids can't contain with `_`.
+///
+/// Ids can't start with a digit, hyphen or period either, so those get a `_` before them.
+fn svg_id(value: &str) -> String {
+    let id = value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    match id.chars().next() {
+        Some(c) if c.is_alphabetic() || c == '_' => id,
+        _ => format!("_{id}"),
+    }
+}
+
+/// Says which file an error happened in.
+fn in_file(path: &std::path::Path, e: RenderError) -> String {
+    format!("{}: {e}", path.display())
+}
+
+/// Refuses to draw more features than `--max-features`.
+fn check_feature_count(max: Option<usize>, count: usize) -> Result<(), String> {
+    match max {
+        Some(max) if count > max => Err(format!(
+            "{count} features selected, more than --max-features {max}; try a tighter selection"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// What to say about an id listed as a `listed_kind` that was `found` to be another kind.
+fn mistyped_message(found: OsmId, listed_kind: &str) -> String {
+    format!(
+        "{listed_kind} {0} not found: {0} is a {1}, not a {listed_kind}",
+        found.inner_id(),
+        kind(found)
+    )
+}
+
+/// Reports a problem on stderr, as text or as a JSON object with its kind and the id it concerns.
+fn warn(log_format: LogFormat, kind: &str, id: i64, message: String) {
+    eprintln!("{}", log_line(log_format, kind, id, message));
+}
+
+/// The line [`warn`] reports a problem with.
+fn log_line(log_format: LogFormat, kind: &str, id: i64, message: String) -> String {
+    match log_format {
+        LogFormat::Text => message,
+        LogFormat::Json => json!({ "kind": kind, "id": id, "message": message }).to_string(),
+    }
+}
+
+/// Reads `--filter` rules into the way and relation tag filters they add.
+///
+/// Blank lines and lines starting with `#` are skipped; malformed rules are warned about,
+/// with their line number as the id.
+fn read_filter(
+    path: &std::path::Path,
+    log_format: LogFormat,
+) -> Result<(Vec<TagFilter>, Vec<TagFilter>), Box<dyn Error>> {
+    let (mut ways, mut relations) = (Vec::new(), Vec::new());
+    for (i, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let rule = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| format!("expected a type and key=value, got {line:?}"))
+            .and_then(|(kind, filter)| Ok((kind, parse_tag_filter(filter.trim())?)));
+        match rule {
+            Ok(("way", filter)) => ways.push(filter),
+            Ok(("relation", filter)) => relations.push(filter),
+            Ok((kind, _)) => {
+                let message = format!(
+                    "{}:{}: expected way or relation, got {kind:?}",
+                    path.display(),
+                    i + 1
+                );
+                warn(log_format, "bad_filter", i as i64 + 1, message);
+            }
+            Err(e) => {
+                let message = format!("{}:{}: {e}", path.display(), i + 1);
+                warn(log_format, "bad_filter", i as i64 + 1, message);
+            }
+        }
+    }
+    Ok((ways, relations))
+}
+
+/// How a way differs between the extract and the one given to `--diff`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    Added,
+    Removed,
+    /// Its tags or the positions of its nodes changed.
+    Modified,
+}
+impl Change {
+    fn color(self) -> Rgb {
+        match self {
+            Change::Added => Rgb(0x00, 0xa0, 0x00),
+            Change::Removed => Rgb(0xe0, 0x00, 0x00),
+            Change::Modified => Rgb(0xff, 0xa5, 0x00),
+        }
+    }
+}
+
+/// Compares the selected ways with those in an older extract.
+///
+/// Removed ways are taken from the older extract and added to the selection, so they are drawn.
+fn diff(
+    path: &std::path::Path,
+    objs: &mut BTreeMap<OsmId, OsmObj>,
+    ways: &mut BTreeSet<WayId>,
+    relations: &BTreeSet<RelationId>,
+) -> Result<BTreeMap<WayId, Change>, Box<dyn Error>> {
+    let old = OsmPbfReader::new(File::open(path)?).get_objs_and_deps(|o| match o {
+        OsmObj::Node(_) => false,
+        // Ways only drawn as members are looked up too, so their absence means they're new.
+        OsmObj::Way(way) => ways.contains(&way.id) || objs.contains_key(&OsmId::Way(way.id)),
+        OsmObj::Relation(rel) => relations.contains(&rel.id),
+    })?;
+    let geometry = |objs: &BTreeMap<OsmId, OsmObj>, way: &Way| {
+        way.nodes
+            .iter()
+            .map(|n| {
+                let node = objs.get(&OsmId::Node(*n))?.node()?;
+                Some((node.decimicro_lat, node.decimicro_lon))
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut changes = BTreeMap::new();
+    for way in objs.values().filter_map(OsmObj::way) {
+        let change = match old.get(&OsmId::Way(way.id)).and_then(OsmObj::way) {
+            None => Change::Added,
+            Some(old_way)
+                if old_way.tags != way.tags || geometry(&old, old_way) != geometry(objs, way) =>
+            {
+                Change::Modified
+            }
+            Some(_) => continue,
+        };
+        changes.insert(way.id, change);
+    }
+    for way in old.values().filter_map(OsmObj::way) {
+        if objs.contains_key(&OsmId::Way(way.id)) {
+            continue;
+        }
+        changes.insert(way.id, Change::Removed);
+        ways.insert(way.id);
+        for n in &way.nodes {
+            if let Some(node) = old.get(&OsmId::Node(*n)) {
+                objs.entry(node.id()).or_insert_with(|| node.clone());
+            }
+        }
+        objs.insert(OsmId::Way(way.id), OsmObj::Way(way.clone()));
+    }
+    Ok(changes)
+}
+
+/// The extent of a relation's nodes, including those of its ways and member relations.
+fn relation_bound(
+    objs: &BTreeMap<OsmId, OsmObj>,
+    nodes: &impl NodeSource,
+    id: RelationId,
+) -> Bound {
+    let mut bound = Bound::new();
+    let mut stack = vec![id];
+    let mut seen = BTreeSet::new();
+    while let Some(id) = stack.pop() {
+        if !seen.insert(id) {
+            continue;
+        }
+        let Some(rel) = objs.get(&OsmId::Relation(id)).and_then(OsmObj::relation) else {
+            continue;
+        };
+        for r in &rel.refs {
+            match objs.get(&r.member) {
+                Some(OsmObj::Node(node)) => bound.update(node),
+                Some(OsmObj::Way(way)) => {
+                    for node in way.nodes.iter().filter_map(|n| nodes.node(*n)) {
+                        bound.update(&node);
+                    }
+                }
+                Some(OsmObj::Relation(child)) => stack.push(child.id),
+                None => {}
+            }
+        }
+    }
+    bound
+}
+
+/// The ways and relations to draw, with all the objects they're made of.
+struct Selection {
+    objs: BTreeMap<OsmId, OsmObj>,
+    ways: BTreeSet<WayId>,
+    relations: BTreeSet<RelationId>,
+    /// When the objects were last edited, if `--data-date` is to go by it.
+    edit_times: BTreeMap<OsmId, i64>,
+}
+
+/// Reads what to draw from the extract: the listed `ways` and `relations`,
+/// narrowed down or added to by the other options selecting features.
+///
+/// `--bbox-from-relation` sets `--bbox` from the relation it names.
+fn select(
+    args: &mut Args,
+    mut ways: BTreeSet<WayId>,
+    mut relations: BTreeSet<RelationId>,
+) -> Result<Selection, RenderError> {
+    let file = File::open(&args.data)?;
+    let reading = progress_bar(args.progress, file.metadata()?.len(), READING_TEMPLATE);
+    let mut pbf = OsmPbfReader::new(ReadProgress {
+        inner: file,
+        progress: reading.clone(),
+    });
+    // `--user` and `--changeset` narrow down what the ids and tag filters select,
+    // or select by themselves without either.
+    let edited = if args.user.is_some() || args.changeset.is_some() {
+        let matches = metadata_matches(&mut pbf, args)?;
+        let unfiltered = args.way_tag.is_empty() && args.rel_tag.is_empty();
+        if args.ways.is_none() && args.relations.is_none() && unfiltered {
+            ways = matches.iter().filter_map(OsmId::way).collect();
+            relations = matches.iter().filter_map(OsmId::relation).collect();
+        } else {
+            ways.retain(|w| matches.contains(&OsmId::Way(*w)));
+            relations.retain(|r| matches.contains(&OsmId::Relation(*r)));
+        }
+        Some(matches)
+    } else {
+        None
+    };
+    let edited_match = |id: OsmId| edited.as_ref().is_none_or(|m| m.contains(&id));
+    let mut tagged = BTreeSet::new();
+    // Listed ids that turn out to be of the other kind, to point out if nothing has the right one.
+    let mut mistyped = BTreeSet::new();
+    // The member ways of the relations `--invert` leaves out, which aren't to be drawn by themselves.
+    let mut excluded_members = BTreeSet::new();
+    let mut objs = pbf.get_objs_and_deps(|o| match o {
+        osmpbfreader::OsmObj::Node(_) => false,
+        osmpbfreader::OsmObj::Way(way) => {
+            if args.way_tag.iter().any(|f| f.matches(&way.tags)) && edited_match(o.id()) {
+                tagged.insert(o.id());
+            }
+            if relations.contains(&RelationId(way.id.0)) {
+                mistyped.insert(o.id());
+            }
+            ways.contains(&way.id) != args.invert || tagged.contains(&o.id())
+        }
+        osmpbfreader::OsmObj::Relation(relation) => {
+            if args.rel_tag.iter().any(|f| f.matches(&relation.tags)) && edited_match(o.id()) {
+                tagged.insert(o.id());
+            }
+            if ways.contains(&WayId(relation.id.0)) {
+                mistyped.insert(o.id());
+            }
+            if args.invert && relations.contains(&relation.id) {
+                excluded_members.extend(relation.refs.iter().filter_map(|r| r.member.way()));
+            }
+            relations.contains(&relation.id) != args.invert
+                || args.crop_to_relation == Some(relation.id.0)
+                || args.bbox_from_relation == Some(relation.id.0)
+                || args.center_on_relation == Some(relation.id.0)
+                || tagged.contains(&o.id())
+        }
+    })?;
+    reading.finish_and_clear();
+    if !args.invert {
+        for id in &mistyped {
+            let (listed, listed_kind) = match *id {
+                OsmId::Way(w) => (OsmId::Relation(RelationId(w.0)), "relation"),
+                OsmId::Relation(r) => (OsmId::Way(WayId(r.0)), "way"),
+                OsmId::Node(_) => continue,
+            };
+            if objs.contains_key(&listed) {
+                continue;
+            }
+            warn(
+                args.log_format,
+                &format!("mistyped_{listed_kind}"),
+                id.inner_id(),
+                mistyped_message(*id, listed_kind),
+            );
+            match listed {
+                OsmId::Way(w) => ways.remove(&w),
+                OsmId::Relation(r) => relations.remove(&r),
+                OsmId::Node(_) => false,
+            };
+        }
+    }
+    if args.swap_latlon {
+        for node in objs.values_mut().filter_map(|o| match o {
+            OsmObj::Node(node) => Some(node),
+            _ => None,
+        }) {
+            std::mem::swap(&mut node.decimicro_lat, &mut node.decimicro_lon);
+        }
+    }
+    if args.invert {
+        // Every way and relation that wasn't listed was selected, so it's in `objs`.
+        ways = objs
+            .keys()
+            .filter_map(OsmId::way)
+            .filter(|w| !ways.contains(w))
+            .collect();
+        relations = objs
+            .keys()
+            .filter_map(OsmId::relation)
+            .filter(|r| !relations.contains(r))
+            .collect();
+        // Ways in the selected relations are drawn as part of them, not again by themselves.
+        let members = relations
+            .iter()
+            .filter_map(|r| match objs.get(&OsmId::Relation(*r)) {
+                Some(OsmObj::Relation(rel)) => Some(rel.refs.iter().filter_map(|r| r.member.way())),
+                _ => None,
+            })
+            .flatten()
+            .collect::<BTreeSet<_>>();
+        ways.retain(|w| !excluded_members.contains(w) && !members.contains(w));
+    }
+    ways.extend(tagged.iter().filter_map(OsmId::way));
+    relations.extend(tagged.iter().filter_map(OsmId::relation));
+    if let Some(id) = args.bbox_from_relation {
+        let id = OsmId::Relation(RelationId(id));
+        if !objs.contains_key(&id) {
+            return Err(RenderError::MissingReference { id });
+        }
+        let bound = relation_bound(&objs, &objs, RelationId(id.inner_id()));
+        if bound.is_empty() {
+            return Err(RenderError::NothingToDraw { id });
+        }
+        args.bbox = Some(bound);
+    }
+    let edit_times = match args.data_date {
+        Some(None) => edit_times(&mut pbf, &objs)?,
+        _ => BTreeMap::new(),
+    };
+    if ways.is_empty() && relations.is_empty() {
+        return Err(RenderError::EmptySelection);
+    }
+    Ok(Selection {
+        objs,
+        ways,
+        relations,
+        edit_times,
+    })
+}
+
+/// Finds the ways and relations whose last edit matches `--user` and `--changeset`.
+///
+/// `osmpbfreader`'s objects don't carry their metadata, so this reads the raw blocks.
+fn metadata_matches<R: Read + Seek>(
+    pbf: &mut OsmPbfReader<R>,
+    args: &Args,
+) -> Result<BTreeSet<OsmId>, RenderError> {
+    let mut matches = BTreeSet::new();
+    for block in pbf.primitive_blocks() {
+        let block = block?;
+        let strings = block.get_stringtable().get_s();
+        let is_match = |info: &Info| {
+            args.user.as_ref().is_none_or(|user| {
+                strings
+                    .get(info.get_user_sid() as usize)
+                    .is_some_and(|s| s == user.as_bytes())
+            }) && args.changeset.is_none_or(|c| info.get_changeset() == c)
+        };
+        for group in block.get_primitivegroup() {
+            matches.extend(
+                group
+                    .get_ways()
+                    .iter()
+                    .filter(|w| is_match(w.get_info()))
+                    .map(|w| OsmId::Way(WayId(w.get_id()))),
+            );
+            matches.extend(
+                group
+                    .get_relations()
+                    .iter()
+                    .filter(|r| is_match(r.get_info()))
+                    .map(|r| OsmId::Relation(RelationId(r.get_id()))),
+            );
+        }
+    }
+    pbf.rewind()?;
+    Ok(matches)
+}
+
+/// When each of the objects in `objs` was last edited, in Unix seconds, for those that say.
+///
+/// Like [`metadata_matches`], this reads the raw blocks for the edit times.
+fn edit_times<R: Read + Seek>(
+    pbf: &mut OsmPbfReader<R>,
+    objs: &BTreeMap<OsmId, OsmObj>,
+) -> Result<BTreeMap<OsmId, i64>, RenderError> {
+    pbf.rewind()?;
+    let mut times = BTreeMap::new();
+    for block in pbf.primitive_blocks() {
+        let block = block?;
+        let granularity = i64::from(block.get_date_granularity());
+        for group in block.get_primitivegroup() {
+            let dense = group.get_dense();
+            // Dense nodes give each id and time as the difference from the previous node's.
+            let running = |sum: &mut i64, delta: &i64| {
+                *sum += delta;
+                Some(*sum)
+            };
+            let dense_ids = dense.get_id().iter().scan(0, running);
+            let dense_times = dense
+                .get_denseinfo()
+                .get_timestamp()
+                .iter()
+                .scan(0, running);
+            let nodes = group
+                .get_nodes()
+                .iter()
+                .map(|n| (n.get_id(), n.get_info().get_timestamp()));
+            let nodes = dense_ids
+                .zip(dense_times)
+                .chain(nodes)
+                .map(|(id, time)| (OsmId::Node(NodeId(id)), time));
+            let ways = group
+                .get_ways()
+                .iter()
+                .map(|w| (OsmId::Way(WayId(w.get_id())), w.get_info().get_timestamp()));
+            let relations = group.get_relations().iter().map(|r| {
+                let id = OsmId::Relation(RelationId(r.get_id()));
+                (id, r.get_info().get_timestamp())
+            });
+            times.extend(
+                nodes
+                    .chain(ways)
+                    .chain(relations)
+                    .filter(|(id, time)| *time > 0 && objs.contains_key(id))
+                    .map(|(id, time)| (id, time * granularity / 1000)),
+            );
+        }
+    }
+    pbf.rewind()?;
+    Ok(times)
+}
+
+/// Formats Unix seconds as their date in UTC, like `2024-03-01`.
+fn iso_date(seconds: i64) -> String {
+    // Counting from 0000-03-01 puts leap days at the end of each year.
+    let days = seconds.div_euclid(86400) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Where a way is drawn through, ahead of simplifying it.
+struct WayGeometry {
+    /// The way's nodes that are in the extract, without repeats.
+    nodes: Vec<Node>,
+    /// Where among `points` each of the `nodes` is.
+    positions: Vec<usize>,
+    /// The projected points, including those `--densify` adds between the nodes.
+    points: Vec<(f64, f64)>,
+    /// Whether `--close-areas` closed the way.
+    auto_closed: bool,
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    ways: usize,
+    short_ways: usize,
+    /// Member relations that aren't in the extract.
+    missing_relations: usize,
+    /// Relations that are present, but none of whose geometry was drawn.
+    empty_relations: usize,
+    /// Relations left out for being entirely outside `--bbox`.
+    clipped_relations: usize,
+    bounds: Option<(f64, f64, f64, f64)>,
+}
+impl Stats {
+    fn print(&self) {
+        eprintln!("ways rendered: {}", self.ways);
+        eprintln!("ways shorter than --min-length: {}", self.short_ways);
+        eprintln!(
+            "member relations not in the extract: {}",
+            self.missing_relations
+        );
+        eprintln!("relations with nothing to draw: {}", self.empty_relations);
+        eprintln!("relations outside --bbox: {}", self.clipped_relations);
+        if let Some((min_lon, min_lat, max_lon, max_lat)) = self.bounds {
+            eprintln!("bounds: {min_lon},{min_lat},{max_lon},{max_lat}");
+        }
+    }
+}
+
+struct Renderer<'a, S: NodeSource = BTreeMap<OsmId, OsmObj>> {
+    objs: &'a BTreeMap<OsmId, OsmObj>,
+    /// Where the coordinates of the nodes drawn come from.
+    nodes: &'a S,
+    /// How the ways differ from the extract given to `--diff`.
+    changes: &'a BTreeMap<WayId, Change>,
+    /// When the objects were last edited, in Unix seconds, for `--data-date` to go by.
+    edit_times: &'a BTreeMap<OsmId, i64>,
+    /// The newest of the edit times of what was drawn.
+    newest_edit: Option<i64>,
+    projection: Box<dyn Projection>,
+    args: &'a Args,
+    bound: Bound,
+    stats: Stats,
+    /// Elevations of the rendered features and their range, when coloring by elevation.
+    eles: BTreeMap<OsmId, f64>,
+    ele_range: Option<Range<f64>>,
+    /// The color of the relation each way belongs to, when coloring by membership.
+    member_colors: BTreeMap<OsmId, String>,
+    /// Whether we're inside a highlighted feature.
+    highlighting: bool,
+    /// How many relations deep we are in a selected one.
+    depth: usize,
+    /// Tolerance to simplify ways with.
+    tolerance: Option<f64>,
+    /// The ways drawn as part of a filled relation's rings.
+    ring_members: BTreeSet<WayId>,
+    /// The values of the `--layers-by` tag and their layers' ids, in the order they're drawn.
+    layers: Vec<(String, String)>,
+    /// The ids made from tag values so far, which mustn't be given out twice.
+    tag_ids: BTreeSet<String>,
+    /// The ways and relations drawn.
+    rendered: BTreeSet<OsmId>,
+    /// The projected coordinates of the nodes drawn, for `--reproject-only`.
+    projected: BTreeMap<NodeId, (f64, f64)>,
+    /// Whether the selection crosses the antimeridian and is drawn continuously across it.
+    unwrap: bool,
+    /// The gradients of the ways drawn with `--gradient-along`.
+    gradients: BTreeMap<WayId, LinearGradient>,
+    /// The ids of the markers drawn, which need defining.
+    markers: BTreeSet<&'static str>,
+    /// The first and last nodes of the ways drawn, with the ways ending at each.
+    endpoints: BTreeMap<NodeId, ((f64, f64), BTreeSet<WayId>)>,
+    /// The color of the way being drawn from its direction in a route, for `--color-by-role`.
+    role_color: Option<Rgb>,
+    /// The points of the way last drawn, for `--curvature-color` to split it into its stretches.
+    drawn_points: Vec<(f64, f64)>,
+    /// The projected points of every node drawn, for `--hull`.
+    hull_points: Vec<(f64, f64)>,
+    /// The projected points of the nodes drawn in the relation being drawn, for `--relation-outline`.
+    outline_points: Vec<(f64, f64)>,
+    /// How many missing objects have been warned about, for `--max-warnings`.
+    missing_warnings: Cell<usize>,
+    /// Nodes in more than one way, when keeping them through simplification.
+    shared_nodes: BTreeSet<NodeId>,
+    /// The middle of the relation to center on, once it is drawn.
+    center: Option<(f64, f64)>,
+    /// Nodes where shared edges begin or end, when preserving topology.
+    junctions: BTreeSet<NodeId>,
+    /// Which points of each shared chain of nodes survive simplification.
+    chains: BTreeMap<Vec<NodeId>, Vec<bool>>,
+}
+
+impl<'a, S: NodeSource> Renderer<'a, S> {
+    fn new(
+        objs: &'a BTreeMap<OsmId, OsmObj>,
+        nodes: &'a S,
+        changes: &'a BTreeMap<WayId, Change>,
+        edit_times: &'a BTreeMap<OsmId, i64>,
+        args: &'a Args,
+        tolerance: Option<f64>,
+    ) -> Self {
+        let mut eles = BTreeMap::new();
+        if args.ele_color {
+            eles.extend(objs.values().filter_map(|o| match o {
+                OsmObj::Way(way) => Some((o.id(), way_ele(nodes, way)?)),
+                OsmObj::Relation(rel) => Some((o.id(), ele(&rel.tags)?)),
+                OsmObj::Node(_) => None,
+            }));
+        }
+        let ele_range = eles.values().fold(None, |range: Option<Range<f64>>, &e| {
+            Some(range.map_or(e..e, |r| r.start.min(e)..r.end.max(e)))
+        });
+
+        let mut member_colors = BTreeMap::new();
+        if args.color_by_relation {
+            // Relations are visited in id order, so the lowest relation id takes precedence.
+            for rel in objs.values().filter_map(OsmObj::relation) {
+                if let Some(color) = rel.tags.get("colour").filter(|s| s.starts_with('#')) {
+                    for r in rel.refs.iter().filter(|r| r.member.is_way()) {
+                        member_colors
+                            .entry(r.member)
+                            .or_insert_with(|| color.to_string());
+                    }
+                }
+            }
+        }
+
+        Renderer {
+            objs,
+            nodes,
+            changes,
+            edit_times,
+            newest_edit: None,
+            projection: args.projection.projection(),
+            args,
+            bound: Bound::new(),
+            stats: Stats::default(),
+            eles,
+            ele_range,
+            member_colors,
+            highlighting: false,
+            depth: 0,
+            tolerance,
+            junctions: if args.simplify_preserve_topology {
+                junctions(objs)
+            } else {
+                BTreeSet::new()
+            },
+            chains: BTreeMap::new(),
+            center: None,
+            role_color: None,
+            drawn_points: Vec::new(),
+            hull_points: Vec::new(),
+            outline_points: Vec::new(),
+            missing_warnings: Cell::new(0),
+            shared_nodes: if args.simplify_keep_shared {
+                shared_nodes(objs)
+            } else {
+                BTreeSet::new()
+            },
+            ring_members: BTreeSet::new(),
+            layers: Vec::new(),
+            tag_ids: BTreeSet::new(),
+            rendered: BTreeSet::new(),
+            gradients: BTreeMap::new(),
+            unwrap: args.split_antimeridian && {
+                let mut bound = Bound::new();
+                for o in objs.values() {
+                    match o {
+                        OsmObj::Node(node) => bound.update(node),
+                        OsmObj::Way(way) => {
+                            for node in way.nodes.iter().filter_map(|n| nodes.node(*n)) {
+                                bound.update(&node);
+                            }
+                        }
+                        OsmObj::Relation(_) => {}
+                    }
+                }
+                bound.crosses_antimeridian()
+            },
+            projected: BTreeMap::new(),
+            markers: BTreeSet::new(),
+            endpoints: BTreeMap::new(),
+        }
+    }
+
+    /// The SVG element, with the default stroke for everything in it.
+    fn document(&self) -> Document {
+        let svg = Document::new()
+            .set("stroke", "#000000")
+            .set("stroke-width", self.args.stroke_width)
+            .set("stroke-linecap", "round")
+            .set("stroke-linejoin", "round");
+        if self.args.heatmap {
+            svg.set("stroke-opacity", HEATMAP_OPACITY)
+        } else {
+            svg
+        }
+    }
+
+    fn render(&mut self, relations: &BTreeSet<RelationId>, ways: &BTreeSet<WayId>) -> Document {
+        let mut svg = self.document();
+        if self.args.stroke_units == StrokeUnits::Px {
+            svg = svg.add(Style::new("path { vector-effect: non-scaling-stroke; }"));
+        }
+
+        let mut features = relations
+            .iter()
+            .map(|r| OsmId::Relation(*r))
+            .chain(ways.iter().map(|w| OsmId::Way(*w)))
+            .collect::<Vec<_>>();
+        self.sort_features(&mut features);
+        self.ring_members = self.drawn_ring_members(relations);
+        let progress = progress_bar(
+            self.args.progress,
+            features.len() as u64,
+            RENDERING_TEMPLATE,
+        );
+        let mut content = Vec::<Box<dyn svg::Node>>::new();
+        let mut layers = Vec::<(String, String, Group)>::new();
+        let crop = self.args.crop_to_relation.map(RelationId);
+        // The cropping relation is drawn on top of everything it crops, without being cropped.
+        let mut boundary = None;
+        let mut merged = None::<(usize, Group)>;
+        for id in progress.wrap_iter(features.into_iter()) {
+            let Some(node) = self.feature_to_node(id) else {
+                continue;
+            };
+            if crop.is_some_and(|r| id == OsmId::Relation(r)) {
+                boundary = Some(node);
+                continue;
+            }
+            let tags = self.objs[&id].tags();
+            let value = self
+                .args
+                .layers_by
+                .as_ref()
+                .and_then(|key| tags.get(key.as_str()));
+            match value {
+                Some(value) => {
+                    let i = layers
+                        .iter()
+                        .position(|(v, _, _)| v == value)
+                        .unwrap_or_else(|| {
+                            let id = unique_id(&mut self.tag_ids, layer_id(value), id.inner_id());
+                            let group = Group::new()
+                                .set("id", id.as_str())
+                                .set("class", format!("layer {id}"));
+                            layers.push((value.to_string(), id, group));
+                            layers.len() - 1
+                        });
+                    svg::Node::append(&mut layers[i].2, node);
+                }
+                None => match (&mut merged, &self.args.merge_group, id) {
+                    (Some((_, group)), _, OsmId::Relation(_)) => svg::Node::append(group, node),
+                    (None, Some(merge_id), OsmId::Relation(_)) => {
+                        let group = Group::new().set("id", merge_id.as_str()).add(node);
+                        merged = Some((content.len(), group));
+                    }
+                    _ => content.push(node),
+                },
+            }
+        }
+        // The merged group is drawn where the first relation in it would have been.
+        if let Some((i, group)) = merged {
+            content.insert(i, group.into());
+        }
+        // Features without the layer tag are drawn below the layers.
+        self.layers = layers
+            .iter()
+            .map(|(v, id, _)| (v.clone(), id.clone()))
+            .collect();
+        content.extend(layers.into_iter().map(|(_, _, g)| g.into()));
+        if self.args.endpoints {
+            let mut group = Group::new().set("id", "endpoints");
+            for ((x, y), ways) in self.endpoints.values() {
+                // Ends shared by several ways are potential junctions.
+                let id = if ways.len() > 1 {
+                    "junction-marker"
+                } else {
+                    "node-marker"
+                };
+                self.markers.insert(id);
+                group = group.add(
+                    Use::new()
+                        .set("href", format!("#{id}"))
+                        .set("x", *x)
+                        .set("y", *y),
+                );
+            }
+            content.push(group.into());
+        }
+        let mut definitions = Vec::<Box<dyn svg::Node>>::new();
+        let clip = crop.and_then(|id| match self.objs.get(&OsmId::Relation(id)) {
+            Some(OsmObj::Relation(rel)) => {
+                let rings = assemble_rings(self.objs, self.nodes, rel, &*self.projection);
+                let path = self
+                    .rings_to_path(&rings, &rel.tags)
+                    .set("clip-rule", "evenodd");
+                Some(ClipPath::new().set("id", "crop").add(path))
+            }
+            _ => {
+                let message = format!("relation {} to crop to not found", id.0);
+                self.warn("crop_relation_not_found", id.0, message);
+                None
+            }
+        });
+        if self.args.shadow {
+            let shadow = FilterEffectDropShadow::new()
+                .set("dx", STROKE_WIDTH)
+                .set("dy", STROKE_WIDTH)
+                .set("stdDeviation", STROKE_WIDTH)
+                .set("flood-opacity", 0.4);
+            definitions.push(Filter::new().set("id", "shadow").add(shadow).into());
+            let mut group = Group::new().set("filter", "url(#shadow)");
+            for node in content.drain(..) {
+                svg::Node::append(&mut group, node);
+            }
+            content.push(group.into());
+        }
+        if self.args.hull {
+            let hull = convex_hull(&self.hull_points);
+            if let Some(first) = hull.first() {
+                let data = hull[1..]
+                    .iter()
+                    .fold(Data::new().move_to(*first), |d, p| d.line_to(*p))
+                    .close();
+                let path = Path::new()
+                    .set("id", "hull")
+                    .set("d", data)
+                    .set("fill", "#808080")
+                    .set("fill-opacity", 0.2)
+                    .set("stroke", "#808080");
+                content.insert(0, path.into());
+            }
+        }
+        if let Some(spacing) = self.args.graticule {
+            if !self.bound.is_empty() {
+                match graticule(
+                    &self.drawn(&self.bound),
+                    spacing,
+                    self.args.stroke_width,
+                    &|lat, lon| self.project(lat, lon),
+                ) {
+                    Ok(group) => content.insert(0, group.into()),
+                    Err(lines) => {
+                        let message = format!(
+                            "--graticule {spacing} would draw {lines} lines, more than {MAX_GRATICULE_LINES}; not drawing it"
+                        );
+                        self.warn("graticule_too_dense", lines as i64, message);
+                    }
+                }
+            }
+        }
+        if let Some(clip) = clip {
+            definitions.push(clip.into());
+            let mut group = Group::new().set("clip-path", "url(#crop)");
+            for node in content.drain(..) {
+                svg::Node::append(&mut group, node);
+            }
+            content.push(group.into());
+        }
+        content.extend(boundary);
+        let full = self.drawn(&self.bound);
+        if self.args.overview {
+            // The overview draws everything again, without the clipping that follows.
+            let mut group = Group::new().set("id", "overview-source");
+            for node in content.drain(..) {
+                svg::Node::append(&mut group, node);
+            }
+            content.push(group.into());
+        }
+        if let Some(bbox) = &self.args.bbox {
+            let (mut x0, mut y0) = self.project(bbox.lat.end, bbox.lon.start);
+            let (x1, y1) = self.project(bbox.lat.start, bbox.lon.end);
+            let (mut width, mut height) = (x1 - x0, y1 - y0);
+            // Like the view box, a box without extent keeps some room for what's on its edge.
+            if width == 0.0 {
+                x0 -= POINT_MARGIN;
+                width = 2.0 * POINT_MARGIN;
+            }
+            if height == 0.0 {
+                y0 -= POINT_MARGIN;
+                height = 2.0 * POINT_MARGIN;
+            }
+            let rect = Rectangle::new()
+                .set("x", x0)
+                .set("y", y0)
+                .set("width", width)
+                .set("height", height);
+            definitions.push(ClipPath::new().set("id", "bbox").add(rect).into());
+            let mut group = Group::new().set("clip-path", "url(#bbox)");
+            for node in content.drain(..) {
+                svg::Node::append(&mut group, node);
+            }
+            content.push(group.into());
+            self.bound = self.bound.intersection(bbox);
+        }
+
+        let mut view_box = self.view_box(&self.drawn(&self.bound));
+        if view_box.is_some() {
+            self.stats.bounds = Some(self.bound.wgs84());
+        }
+        if let (Some(id), Some((_, _, width, height))) = (self.args.center_on_relation, view_box) {
+            // A relation only loaded to center on isn't drawn, so its extent comes from its members.
+            let center = self.center.or_else(|| {
+                let bound = relation_bound(self.objs, self.nodes, RelationId(id));
+                (!bound.is_empty()).then(|| self.center(&self.drawn(&bound)))
+            });
+            match center {
+                Some((cx, cy)) => {
+                    view_box = Some((cx - width / 2.0, cy - height / 2.0, width, height))
+                }
+                None => {
+                    let message = format!("relation {id} to center on not found");
+                    self.warn("center_relation_not_found", id, message);
+                }
+            }
+        }
+        if let (true, Some((x, y, width, height))) = (self.unwrap, view_box) {
+            // Each side of the antimeridian gets its own group, clipped to its half of the drawing.
+            let antimeridian = PI * SCALE + self.args.offset_x;
+            let side = |id: &str, x: f64, width: f64| {
+                let rect = Rectangle::new()
+                    .set("x", x)
+                    .set("y", y)
+                    .set("width", width)
+                    .set("height", height);
+                ClipPath::new().set("id", format!("{id}-clip")).add(rect)
+            };
+            definitions.push(side("eastern-hemisphere", x, antimeridian - x).into());
+            definitions
+                .push(side("western-hemisphere", antimeridian, x + width - antimeridian).into());
+            let mut content_group = Group::new().set("id", "antimeridian-content");
+            for node in content.drain(..) {
+                svg::Node::append(&mut content_group, node);
+            }
+            let east = Group::new()
+                .set("id", "eastern-hemisphere")
+                .set("clip-path", "url(#eastern-hemisphere-clip)")
+                .add(content_group);
+            let west = Group::new()
+                .set("id", "western-hemisphere")
+                .set("clip-path", "url(#western-hemisphere-clip)")
+                .add(Use::new().set("href", "#antimeridian-content"));
+            content.push(east.into());
+            content.push(west.into());
+        }
+
+        // Each kind of marker is defined once and referenced wherever it is drawn.
+        for &id in &self.markers {
+            definitions.push(self.marker_symbol(id).into());
+        }
+        definitions.extend(
+            std::mem::take(&mut self.gradients)
+                .into_values()
+                .map(|g| g.into()),
+        );
+        if self.args.legend && self.ele_range.is_some() {
+            let ramp = LinearGradient::new()
+                .set("id", "legend-ramp")
+                .add(
+                    Stop::new()
+                        .set("offset", 0)
+                        .set("stop-color", self.args.ele_low.to_string()),
+                )
+                .add(
+                    Stop::new()
+                        .set("offset", 1)
+                        .set("stop-color", self.args.ele_high.to_string()),
+                );
+            definitions.push(ramp.into());
+        }
+        if !definitions.is_empty() {
+            let mut defs = Definitions::new();
+            for node in definitions {
+                svg::Node::append(&mut defs, node);
+            }
+            svg = svg.add(defs);
+        }
+
+        let window = view_box;
+        match (self.args.rotate, view_box) {
+            (Some(degrees), Some((x, y, width, height))) => {
+                let (cx, cy) = (x + width / 2.0, y + height / 2.0);
+                let mut group =
+                    Group::new().set("transform", format!("rotate({degrees} {cx} {cy})"));
+                for node in content {
+                    svg::Node::append(&mut group, node);
+                }
+                svg = svg.add(group);
+                // The rotated rectangle's bounding box, about the same center.
+                let (sin, cos) = degrees.to_radians().sin_cos();
+                let rotated_width = width * cos.abs() + height * sin.abs();
+                let rotated_height = width * sin.abs() + height * cos.abs();
+                view_box = Some((
+                    cx - rotated_width / 2.0,
+                    cy - rotated_height / 2.0,
+                    rotated_width,
+                    rotated_height,
+                ));
+            }
+            _ => {
+                for node in content {
+                    svg = svg.add(node);
+                }
+            }
+        }
+        if let (true, Some(view_box), Some(window)) = (self.args.overview, view_box, window) {
+            if !full.is_empty() {
+                svg = svg.add(self.overview(&full, view_box, window));
+            }
+        }
+        if let (true, Some(range), Some((x, y, _, _))) =
+            (self.args.legend, &self.ele_range, view_box)
+        {
+            svg = svg.add(ramp_legend(range, x, y));
+        }
+        if let (true, Some(view_box)) = (self.args.scale_bar, view_box) {
+            let lat = (self.bound.lat.start + self.bound.lat.end) / 2.0;
+            let bar = scale_bar(
+                view_box,
+                lat,
+                self.args.scale_bar_units,
+                self.args.stroke_width,
+            );
+            svg = svg.add(bar);
+        }
+        if let (Some(text), Some((x, y, width, height))) = (&self.args.attribution, view_box) {
+            let size = FONT_SIZE / 2.0;
+            let credit = Text::new(text.as_str())
+                .set("id", "attribution")
+                .set("x", x + width - size)
+                .set("y", y + height - size)
+                .set("font-size", size)
+                .set("text-anchor", "end")
+                .set("stroke", "none")
+                .set("fill", "#000000");
+            svg = svg.add(credit);
+        }
+        let date = match &self.args.data_date {
+            Some(Some(date)) => Some(date.clone()),
+            Some(None) => {
+                let date = self.newest_edit.map(iso_date);
+                if date.is_none() {
+                    let message = "no edit times for what's drawn to date the data by".to_string();
+                    self.warn("no_timestamps", 0, message);
+                }
+                date
+            }
+            None => None,
+        };
+        if let (Some(date), Some((x, y, _, height))) = (date, view_box) {
+            let size = FONT_SIZE / 2.0;
+            let note = Text::new(format!("data as of {date}"))
+                .set("id", "data-date")
+                .set("x", x + size)
+                .set("y", y + height - size)
+                .set("font-size", size)
+                .set("stroke", "none")
+                .set("fill", "#000000");
+            svg = svg.add(note);
+        }
+        if let Some(view_box) = view_box.filter(|_| !self.args.no_viewbox) {
+            svg = svg.set("viewBox", view_box);
+        }
+        self.warn_about_rest();
+        progress.finish_and_clear();
+        svg
+    }
+
+    /// Writes the SVG to `out` a feature at a time, with the view box worked out beforehand.
+    ///
+    /// Definitions, like markers, go before the features but are only known once they're drawn,
+    /// so the features wait in a temporary file until the definitions are written.
+    fn stream(
+        &mut self,
+        relations: &BTreeSet<RelationId>,
+        ways: &BTreeSet<WayId>,
+        out: &mut dyn Write,
+    ) -> std::io::Result<()> {
+        let spill_path =
+            std::env::temp_dir().join(format!("osm-rels-svg-{}.features.tmp", std::process::id()));
+        let spill = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&spill_path)?;
+        let result = self.stream_via(relations, ways, spill, out);
+        let _ = std::fs::remove_file(&spill_path);
+        result
+    }
+
+    /// Does the work of [`Renderer::stream`], with `spill` to hold the features in.
+    fn stream_via(
+        &mut self,
+        relations: &BTreeSet<RelationId>,
+        ways: &BTreeSet<WayId>,
+        spill: File,
+        out: &mut dyn Write,
+    ) -> std::io::Result<()> {
+        let mut features = relations
+            .iter()
+            .map(|r| OsmId::Relation(*r))
+            .chain(ways.iter().map(|w| OsmId::Way(*w)))
+            .collect::<Vec<_>>();
+        self.sort_features(&mut features);
+        self.ring_members = self.drawn_ring_members(relations);
+        let mut svg = self.document();
+        let bound = self.extent(&features);
+        if let Some(view_box) = self.view_box(&bound).filter(|_| !self.args.no_viewbox) {
+            svg = svg.set("viewBox", view_box);
+            let (x, y, width, height) = view_box;
+            let reach = reach([x, y, width, height]);
+            if let Some(max) = self.args.max_coord.filter(|max| reach > *max) {
+                let message = format!(
+                    "the drawing reaches {reach} from the origin, beyond --max-coord {max}; try --offset-x and --offset-y"
+                );
+                self.warn("coordinates_too_large", 0, message);
+            }
+        }
+
+        let progress = progress_bar(
+            self.args.progress,
+            features.len() as u64,
+            RENDERING_TEMPLATE,
+        );
+        let mut spill = std::io::BufWriter::new(spill);
+        let mut drawn = false;
+        for id in progress.wrap_iter(features.into_iter()) {
+            if let Some(node) = self.feature_to_node(id) {
+                write!(spill, "\n{node}")?;
+                drawn = true;
+            }
+        }
+        if !self.bound.is_empty() {
+            self.stats.bounds = Some(self.bound.wgs84());
+        }
+        let mut defs = Definitions::new();
+        for &id in &self.markers {
+            defs = defs.add(self.marker_symbol(id));
+        }
+        for gradient in std::mem::take(&mut self.gradients).into_values() {
+            defs = defs.add(gradient);
+        }
+
+        let mut attributes = svg.get_attributes().iter().collect::<Vec<_>>();
+        attributes.sort_by_key(|(name, _)| name.as_str());
+        write!(out, "<svg")?;
+        for (name, value) in attributes {
+            write!(out, " {name}=\"{}\"", escape_html(value))?;
+        }
+        // Like any element, the SVG is only closed in its start tag if nothing goes in it.
+        let mut empty = true;
+        let mut open = |out: &mut dyn Write| {
+            if std::mem::take(&mut empty) {
+                write!(out, ">")?;
+            }
+            Ok::<_, std::io::Error>(())
+        };
+        if self.args.stroke_units == StrokeUnits::Px {
+            let style = Style::new("path { vector-effect: non-scaling-stroke; }");
+            open(out)?;
+            write!(out, "\n{style}")?;
+        }
+        if !defs.get_children().is_empty() {
+            open(out)?;
+            write!(out, "\n{defs}")?;
+        }
+        if drawn {
+            open(out)?;
+            let mut spill = spill.into_inner().map_err(|e| e.into_error())?;
+            spill.rewind()?;
+            std::io::copy(&mut spill, out)?;
+        }
+        if empty {
+            write!(out, "/>")?;
+        } else {
+            write!(out, "\n</svg>")?;
+        }
+        self.warn_about_rest();
+        progress.finish_and_clear();
+        Ok(())
+    }
+    /// The definition of a marker: a dot, or one of the `--icons`.
+    fn marker_symbol(&self, id: &str) -> Symbol {
+        let symbol = Symbol::new().set("id", id).set("overflow", "visible");
+        let Some((_, tags, glyph)) = ICONS.iter().find(|(icon, _, _)| *icon == id) else {
+            let radius = if id == "junction-marker" { 3.0 } else { 2.0 };
+            let marker = Circle::new()
+                .set("r", radius * self.args.stroke_width)
+                .set("stroke", "none");
+            return symbol.add(marker);
+        };
+        // Icons are colored by the key they stand for, like map symbols usually are.
+        let color = match tags[0].split_once('=').map(|(key, _)| key) {
+            Some("shop") => "#a04c9c",
+            Some("tourism") => "#0a7f7a",
+            _ => "#2f6bbf",
+        };
+        let icon = Group::new()
+            .set(
+                "transform",
+                format!("scale({})", 0.75 * self.args.stroke_width),
+            )
+            .add(
+                Circle::new()
+                    .set("r", 6)
+                    .set("fill", color)
+                    .set("stroke", "none"),
+            )
+            .add(
+                Path::new()
+                    .set("d", *glyph)
+                    .set("fill", "none")
+                    .set("stroke", "#ffffff")
+                    .set("stroke-width", 1.2),
+            );
+        symbol.add(icon)
+    }
+
+    /// Draws a way or relation that was asked for, unless there's nothing to draw.
+    fn feature_to_node(&mut self, id: OsmId) -> Option<Box<dyn svg::Node>> {
+        match self.objs.get(&id) {
+            Some(OsmObj::Relation(rel)) => {
+                let group = self.relation_to_group(rel)?;
+                Some(self.link(id, group))
+            }
+            Some(OsmObj::Way(way))
+                if self.ring_members.contains(&way.id) && !self.args.draw_ring_members =>
+            {
+                None
+            }
+            Some(OsmObj::Way(way)) => {
+                let node = self.way_to_node(way)?;
+                Some(self.link(id, node))
+            }
+            _ => {
+                let message = format!("{} {} not found", kind(id), id.inner_id());
+                self.warn(&format!("missing_{}", kind(id)), id.inner_id(), message);
+                None
+            }
+        }
+    }
+
+    /// The extent of the nodes the given features will draw, ahead of drawing them.
+    fn extent(&self, features: &[OsmId]) -> Bound {
+        let mut bound = Bound::new();
+        let mut seen = BTreeSet::new();
+        for id in features {
+            match self.objs.get(id) {
+                Some(OsmObj::Relation(rel)) => bound.extend(&self.relation_extent(rel, &mut seen)),
+                Some(OsmObj::Way(way)) => self.way_extent(way, &mut bound),
+                _ => {}
+            }
+        }
+        bound
+    }
+
+    /// The ways in the rings of the filled relations drawn, counting member relations,
+    /// but not relations only loaded to crop or bound the drawing by.
+    fn drawn_ring_members(&self, relations: &BTreeSet<RelationId>) -> BTreeSet<WayId> {
+        let mut members = BTreeSet::new();
+        if !self.args.fill || !self.renders_member(MemberType::Way) {
+            return members;
+        }
+        let mut seen = BTreeSet::new();
+        let mut stack = relations.iter().copied().collect::<Vec<_>>();
+        while let Some(id) = stack.pop() {
+            let Some(OsmObj::Relation(rel)) = self.objs.get(&OsmId::Relation(id)) else {
+                continue;
+            };
+            // A relation left out draws none of its rings, so its ways still need drawing.
+            if !seen.insert(id) || self.left_out(rel).is_some() {
+                continue;
+            }
+            if is_area_relation(rel) {
+                let rings = assemble_rings(self.objs, self.nodes, rel, &*self.projection);
+                members.extend(rings.into_iter().flat_map(|ring| ring.ways));
+            }
+            if self.renders_member(MemberType::Relation) {
+                stack.extend(rel.refs.iter().filter_map(|r| r.member.relation()));
+            }
+        }
+        members
+    }
+
+    /// The extent of a relation's drawn members, grown the way drawing it grows it.
+    fn relation_extent(&self, rel: &Relation, seen: &mut BTreeSet<RelationId>) -> Bound {
+        let mut bound = Bound::new();
+        if !seen.insert(rel.id) || self.incomplete(rel).is_some() {
+            return bound;
+        }
+        // Rings are drawn whole, so none of their ways are too short to draw.
+        let mut ring_ways = BTreeSet::new();
+        if self.args.min_length.is_some()
+            && self.args.fill
+            && is_area_relation(rel)
+            && self.renders_member(MemberType::Way)
+        {
+            let rings = assemble_rings(self.objs, self.nodes, rel, &*self.projection);
+            ring_ways.extend(rings.into_iter().flat_map(|ring| ring.ways));
+        }
+        for r in &rel.refs {
+            if !self.renders_member(MemberType::of(r.member)) {
+                continue;
+            }
+            match self.objs.get(&r.member) {
+                Some(OsmObj::Way(way)) if ring_ways.contains(&way.id) => {
+                    for node in way.nodes.iter().filter_map(|n| self.nodes.node(*n)) {
+                        bound.update(&node);
+                    }
+                }
+                Some(OsmObj::Way(way)) => self.way_extent(way, &mut bound),
+                Some(OsmObj::Relation(child)) => bound.extend(&self.relation_extent(child, seen)),
+                Some(OsmObj::Node(node))
+                    if self.args.node_markers && !self.excluded(&node.tags) =>
+                {
+                    bound.update(node)
+                }
+                _ => {}
+            }
+        }
+        bound
+    }
+
+    fn way_extent(&self, way: &Way, bound: &mut Bound) {
+        if self.excluded(&way.tags) {
+            return;
+        }
+        // Like drawing it, `--collapse-small` takes the way before `--min-length` can.
+        let collapsed = self.args.collapse_small.is_some_and(|threshold| {
+            way.is_closed() && polygon_area(self.nodes, way, &*self.projection) < threshold
+        });
+        if !collapsed
+            && self.args.min_length.is_some()
+            && self.too_short(&self.way_geometry(way).points)
+        {
+            return;
+        }
+        for node in way.nodes.iter().filter_map(|n| self.nodes.node(*n)) {
+            bound.update(&node);
+        }
+    }
+
+    /// The part of the drawing showing a bound, as `(x, y, width, height)`.
+    fn view_box(&self, bound: &Bound) -> Option<(f64, f64, f64, f64)> {
+        if bound.is_empty() {
+            return None;
+        }
+        let upper_left = self.project(bound.lat.end, bound.lon.start);
+        let lower_right = self.project(bound.lat.start, bound.lon.end);
+        let (mut x, mut y) = upper_left;
+        let (mut width, mut height) = (lower_right.0 - x, lower_right.1 - y);
+        // Point-like geometry has no extent, so give it some room to be visible.
+        if width == 0.0 {
+            x -= POINT_MARGIN;
+            width = 2.0 * POINT_MARGIN;
+        }
+        if height == 0.0 {
+            y -= POINT_MARGIN;
+            height = 2.0 * POINT_MARGIN;
+        }
+        Some((x, y, width, height))
+    }
+
+    /// Why `--require-complete` leaves a relation out, if it does.
+    fn incomplete(&self, rel: &Relation) -> Option<String> {
+        if !self.args.require_complete {
+            return None;
+        }
+        let missing = rel
+            .refs
+            .iter()
+            .filter(|r| !self.objs.contains_key(&r.member))
+            .count();
+        (missing > 0).then(|| {
+            format!(
+                "relation {} left out: {missing} of its {} members missing",
+                rel.id.0,
+                rel.refs.len()
+            )
+        })
+    }
+
+    /// Why a relation is left out of the drawing, if it is, as a warning kind and message.
+    fn left_out(&self, rel: &Relation) -> Option<(&'static str, String)> {
+        if let Some(message) = self.incomplete(rel) {
+            return Some(("incomplete_relation", message));
+        }
+        if let Some(bbox) = &self.args.bbox {
+            let bound = self.relation_extent(rel, &mut BTreeSet::new());
+            if !bound.is_empty() && !bound.intersects(bbox) {
+                let message = format!("relation {} is entirely outside --bbox", rel.id.0);
+                return Some(("clipped_relation", message));
+            }
+        }
+        None
+    }
+
+    fn relation_to_group(&mut self, rel: &Relation) -> Option<Group> {
+        // Checked before drawing anything, so that none of the members count as drawn.
+        if let Some((kind, message)) = self.left_out(rel) {
+            if kind == "clipped_relation" {
+                self.stats.clipped_relations += 1;
+            }
+            self.warn(kind, rel.id.0, message);
+            return None;
+        }
+        self.mark_rendered(OsmId::Relation(rel.id));
+        // Track the relation's own extent, to place its label in the middle of it.
+        let outer_bound = std::mem::replace(&mut self.bound, Bound::new());
+        let outer_points = std::mem::take(&mut self.outline_points);
+        let outer_highlighting = self.highlighting;
+        self.highlighting |= self.args.highlight.contains(&rel.id.0);
+        let mut group = self
+            .set_stroke(Group::new(), OsmId::Relation(rel.id), &rel.tags)
+            .set("id", self.element_id(OsmId::Relation(rel.id), &rel.tags));
+        if self.args.depth_fade && self.depth > 0 {
+            // Opacity compounds, so each level is fainter than the one containing it.
+            group = group.set("opacity", DEPTH_FADE);
+        }
+        if self.args.flow_animate && rel.tags.get("type").is_some_and(|t| t == "route") {
+            // The dashes are inherited by the members' paths, and so is the offset moving them.
+            let animate = Animate::new()
+                .set("attributeName", "stroke-dashoffset")
+                .set("from", 0)
+                .set("to", -(FLOW_DASH + FLOW_GAP))
+                .set("dur", "1s")
+                .set("repeatCount", "indefinite");
+            group = group
+                .set("stroke-dasharray", format!("{FLOW_DASH} {FLOW_GAP}"))
+                .add(animate);
+        }
+        for key in &self.args.group_attrs {
+            if let Some(value) = rel.tags.get(key.as_str()) {
+                group = group.set(data_attribute(key), value.as_str());
+            }
+        }
+        let mut refs = rel.refs.iter().collect::<Vec<_>>();
+        if self.args.deterministic && !is_ordered(rel) {
+            refs.sort_by_key(|r| r.member);
+        }
+        // Member relations go before or after the rest, among members of the same z-order.
+        refs.sort_by_key(|r| {
+            let rank = match (self.args.render_order, r.member.is_relation()) {
+                (RenderOrder::WaysFirst, true) | (RenderOrder::RelationsFirst, false) => 1,
+                _ => 0,
+            };
+            (self.z_order(&r.member), rank)
+        });
+        let mut ring_ways = BTreeSet::new();
+        if self.args.fill && is_area_relation(rel) && self.renders_member(MemberType::Way) {
+            let rings = assemble_rings(self.objs, self.nodes, rel, &*self.projection);
+            ring_ways.extend(rings.iter().flat_map(|ring| ring.ways.iter().copied()));
+            group = group.add(self.rings_to_path(&rings, &rel.tags));
+        }
+        for r in refs {
+            if r.member.way().is_some_and(|w| ring_ways.contains(&w))
+                || !self.renders_member(MemberType::of(r.member))
+            {
+                continue;
+            }
+            let role_color = direction_color(rel, &r.role).filter(|_| self.args.color_by_role);
+            if let Some(r) = self.objs.get(&r.member) {
+                match r {
+                    OsmObj::Way(way) => {
+                        self.role_color = role_color;
+                        if let Some(path) = self.way_to_node(way) {
+                            group = group.add(self.link(OsmId::Way(way.id), path))
+                        }
+                        self.role_color = None;
+                    }
+                    OsmObj::Relation(rel) => {
+                        self.depth += 1;
+                        if let Some(child) = self.relation_to_group(rel) {
+                            group = group.add(self.link(OsmId::Relation(rel.id), child))
+                        }
+                        self.depth -= 1;
+                    }
+                    OsmObj::Node(node) if self.args.node_markers && !self.excluded(&node.tags) => {
+                        self.visit(node);
+                        let (x, y) = self.project_node(node);
+                        // Nodes without an icon of their own, or without `--icons`, get a dot.
+                        let id = icon(&node.tags)
+                            .filter(|_| self.args.icons)
+                            .unwrap_or("node-marker");
+                        self.markers.insert(id);
+                        let marker = Use::new()
+                            .set("href", format!("#{id}"))
+                            .set("x", x)
+                            .set("y", y);
+                        group = group.add(self.link(OsmId::Node(node.id), marker))
+                    }
+                    OsmObj::Node(_) => {}
+                }
+            } else if let OsmId::Relation(child) = r.member {
+                self.stats.missing_relations += 1;
+                let message = format!(
+                    "member relation {} of relation {} not in the extract",
+                    child.0, rel.id.0
+                );
+                self.warn("missing_member_relation", child.0, message);
+            } else {
+                let message = format!("ref {:?} of relation {} not found", r.member, rel.id.0);
+                self.warn("missing_member", r.member.inner_id(), message);
+            }
+        }
+        self.highlighting = outer_highlighting;
+        let bound = std::mem::replace(&mut self.bound, outer_bound);
+        self.bound.extend(&bound);
+        let points = std::mem::replace(&mut self.outline_points, outer_points);
+        // Outlines of containing relations take in the nodes of their member relations too.
+        self.outline_points.extend(&points);
+        if let (Some(shape), false) = (self.args.relation_outline, bound.is_empty()) {
+            let rings = match shape {
+                OutlineShape::Convex => vec![convex_hull(&points)],
+                OutlineShape::Concave => {
+                    let lat = (bound.lat.start + bound.lat.end) / 2.0;
+                    alpha_shape(&points, meters_to_projected(self.args.outline_radius, lat))
+                }
+            };
+            let data =
+                rings
+                    .iter()
+                    .filter(|ring| ring.len() >= 3)
+                    .fold(Data::new(), |data, ring| {
+                        ring[1..]
+                            .iter()
+                            .fold(data.move_to(ring[0]), |d, p| d.line_to(*p))
+                            .close()
+                    });
+            if !data.is_empty() {
+                let color = svg::Node::get_attributes(&group)
+                    .and_then(|a| a.get("stroke"))
+                    .map_or("#808080".to_string(), ToString::to_string);
+                let outline = Path::new()
+                    .set("class", "relation-outline")
+                    .set("d", data)
+                    .set("fill", color)
+                    .set("fill-opacity", 0.2)
+                    .set("fill-rule", "evenodd")
+                    .set("stroke", "none");
+                if let Some(children) = svg::Node::get_children_mut(&mut group) {
+                    children.insert(0, Box::new(outline));
+                }
+            }
+        }
+        if self.args.center_on_relation == Some(rel.id.0) && !bound.is_empty() {
+            self.center = Some(self.center(&self.drawn(&bound)));
+        }
+        if bound.is_empty() {
+            self.stats.empty_relations += 1;
+            let message = format!("relation {} has nothing to draw", rel.id.0);
+            self.warn("empty_relation", rel.id.0, message);
+        }
+        if let Some(name) = rel.tags.get("name").filter(|_| self.args.relation_labels) {
+            if !bound.is_empty() {
+                let (x, y) = self.center(&self.drawn(&bound));
+                let label = Text::new(name.as_str())
+                    .set("x", x)
+                    .set("y", y)
+                    .set("font-size", FONT_SIZE)
+                    .set("text-anchor", "middle")
+                    .set("dominant-baseline", "middle")
+                    .set("stroke", "none")
+                    .set("fill", "#000000");
+                group = group.add(label);
+            }
+        }
+        if self.args.merge_paths {
+            group = merge_paths(group);
+        }
+        Some(group)
+    }
+
+    /// Whether relation members of a kind are drawn, by `--only-types`.
+    fn renders_member(&self, kind: MemberType) -> bool {
+        self.args.only_types.is_empty() || self.args.only_types.contains(&kind)
+    }
+
+    /// Renders a way, along with its casing if enabled.
+    fn way_to_node(&mut self, way: &Way) -> Option<Box<dyn svg::Node>> {
+        if self.excluded(&way.tags) {
+            return None;
+        }
+        if let Some(threshold) = self.args.collapse_small {
+            if way.is_closed() && polygon_area(self.nodes, way, &*self.projection) < threshold {
+                return self.way_to_marker(way).map(|m| m.into());
+            }
+        }
+        let path = self.way_to_path(way)?;
+        if self.args.curvature_color
+            && !self.highlighting
+            && !self.args.highlight.contains(&way.id.0)
+        {
+            return Some(self.curvature_group(path).into());
+        }
+        if !self.args.casing {
+            return Some(path.into());
+        }
+        let attributes = svg::Node::get_attributes(&path).unwrap();
+        let width = attributes
+            .get("stroke-width")
+            .and_then(|w| w.parse::<f64>().ok())
+            .unwrap_or(self.args.stroke_width);
+        let color = attributes
+            .get("stroke")
+            .and_then(|c| parse_color(c).ok())
+            .map_or(Rgb(0, 0, 0), |c| c.lerp(Rgb(0, 0, 0), 0.5));
+        let casing = Path::new()
+            .set("d", attributes["d"].clone())
+            .set("fill", "none")
+            .set("stroke", color.to_string())
+            .set("stroke-width", 2.0 * width);
+        Some(Group::new().add(casing).add(path).into())
+    }
+
+    fn way_geometry(&self, way: &Way) -> WayGeometry {
+        let mut nodes = way
+            .nodes
+            .iter()
+            .filter_map(|n| self.nodes.node(*n))
+            .collect::<Vec<_>>();
+        // Repeated nodes would only make zero-length segments.
+        nodes.dedup_by(|a, b| {
+            a.id == b.id || (a.decimicro_lat, a.decimicro_lon) == (b.decimicro_lat, b.decimicro_lon)
+        });
+        let auto_closed =
+            self.args.close_areas && way.is_open() && is_area(&way.tags) && nodes.len() >= 3;
+        if auto_closed {
+            nodes.push(nodes[0].clone());
+        }
+        // The points `--densify` adds only shape the line, so anything going by node id uses
+        // `nodes`, found among the points at `positions`.
+        let (geometry, positions) = match self.args.densify {
+            Some(max_degrees) => densify(&nodes, max_degrees),
+            None => (Vec::new(), (0..nodes.len()).collect()),
+        };
+        let points = if geometry.is_empty() {
+            &nodes
+        } else {
+            &geometry
+        }
+        .iter()
+        .map(|n| self.project_node(n))
+        .collect();
+        WayGeometry {
+            nodes,
+            positions,
+            points,
+            auto_closed,
+        }
+    }
+
+    /// Whether a way is left out by `--min-length`, given the points it would be drawn through.
+    fn too_short(&self, points: &[(f64, f64)]) -> bool {
+        self.args
+            .min_length
+            .is_some_and(|min_length| polyline_length(points) < min_length)
+    }
+
+    fn way_to_path(&mut self, way: &Way) -> Option<Path> {
+        for n in &way.nodes {
+            if self.nodes.node(*n).is_none() {
+                self.warn("missing_node", n.0, format!("node {} not found", n.0));
+            }
+        }
+        let WayGeometry {
+            nodes,
+            positions,
+            points,
+            auto_closed,
+        } = self.way_geometry(way);
+        if auto_closed {
+            let message = format!(
+                "way {} is tagged as an area but not closed; closing it",
+                way.id.0
+            );
+            self.warn("auto_closed_way", way.id.0, message);
+        }
+        if self.too_short(&points) {
+            self.stats.short_ways += 1;
+            return None;
+        }
+
+        for n in &nodes {
+            self.visit(n);
+        }
+        if self.args.endpoints {
+            for (n, p) in nodes
+                .first()
+                .zip(points.first())
+                .into_iter()
+                .chain(nodes.last().zip(points.last()))
+            {
+                let (_, ways) = self.endpoints.entry(n.id).or_insert((*p, BTreeSet::new()));
+                ways.insert(way.id);
+            }
+        }
+        let points = match self.tolerance {
+            Some(tolerance) => self.simplify_way(&nodes, &positions, points, tolerance),
+            None => points,
+        };
+
+        let clockwise = (self.args.show_winding && way.is_closed() && points.len() >= 4)
+            .then(|| signed_area(&points) > 0.0);
+        // Closed ways run their gradient out to their middle point instead of back to the start.
+        let gradient_end = match (points.first(), points.last()) {
+            (Some(first), Some(last)) if first != last => Some(*last),
+            _ => points.get(points.len() / 2).copied(),
+        };
+        let gradient_span = points.first().copied().zip(gradient_end);
+        // Ways drawn to their tagged width are the filled outline of their centerline instead.
+        let buffer_width = tag_width(&way.tags).filter(|_| self.args.buffer && points.len() >= 2);
+        let points = match buffer_width {
+            Some(width) => {
+                let width = width.clamp(self.args.width_min, self.args.width_max);
+                buffer(&points, meters_to_projected(width, nodes[0].lat()) / 2.0)
+            }
+            None => points,
+        };
+        if self.args.curvature_color {
+            self.drawn_points = points.clone();
+        }
+        let mut data = Data::new();
+        // Relative paths go between points rounded to hundredths, counted in whole hundredths,
+        // so that adding up the deltas gives back each rounded point without rounding errors.
+        let hundredths = |v: f64| (v * 100.0).round() as i64;
+        let mut previous = (0, 0);
+        for (i, p) in points.into_iter().enumerate() {
+            data = match (i, self.args.relative_paths) {
+                (_, true) => {
+                    let rounded = (hundredths(p.0), hundredths(p.1));
+                    let delta = (
+                        (rounded.0 - previous.0) as f64 / 100.0,
+                        (rounded.1 - previous.1) as f64 / 100.0,
+                    );
+                    previous = rounded;
+                    // The first relative move is from the origin, so it's absolute all the same.
+                    if i == 0 {
+                        data.move_by(delta)
+                    } else {
+                        data.line_by(delta)
+                    }
+                }
+                (0, false) => data.move_to(p),
+                (_, false) => data.line_to(p),
+            };
+        }
+        if buffer_width.is_some() {
+            data = data.close();
+        }
+        self.stats.ways += 1;
+        self.mark_rendered(OsmId::Way(way.id));
+        let outer_highlighting = self.highlighting;
+        self.highlighting |= self.args.highlight.contains(&way.id.0);
+        let filled = self.args.fill && (way.is_closed() || auto_closed) && is_area(&way.tags);
+        let fill = if filled {
+            fill_color(&way.tags)
+        } else {
+            "none".to_string()
+        };
+        let mut path = self
+            .set_stroke(Path::new(), OsmId::Way(way.id), &way.tags)
+            .set("id", self.element_id(OsmId::Way(way.id), &way.tags))
+            .set("fill", fill)
+            .set("d", data);
+        if filled {
+            let rule = match self.args.fill_rule {
+                FillRule::Nonzero => "nonzero",
+                FillRule::Evenodd => "evenodd",
+            };
+            path = path.set("fill-rule", rule);
+        }
+        if let Some(((x1, y1), (x2, y2))) = gradient_span.filter(|_| self.args.gradient_along) {
+            if !self.highlighting {
+                let id = format!("gradient-{}", way.id.0);
+                let gradient = LinearGradient::new()
+                    .set("id", id.as_str())
+                    .set("gradientUnits", "userSpaceOnUse")
+                    .set("x1", x1)
+                    .set("y1", y1)
+                    .set("x2", x2)
+                    .set("y2", y2)
+                    .add(
+                        Stop::new()
+                            .set("offset", 0)
+                            .set("stop-color", self.args.gradient_start.to_string()),
+                    )
+                    .add(
+                        Stop::new()
+                            .set("offset", 1)
+                            .set("stop-color", self.args.gradient_end.to_string()),
+                    );
+                self.gradients.insert(way.id, gradient);
+                path = path.set("stroke", format!("url(#{id})"));
+            }
+        }
+        if let (Some(clockwise), false) = (clockwise, self.highlighting) {
+            let (winding, color) = if clockwise {
+                ("clockwise", WINDING_COLORS.0)
+            } else {
+                ("counterclockwise", WINDING_COLORS.1)
+            };
+            path = path
+                .set("stroke", color.to_string())
+                .set("data-winding", winding);
+        }
+        if buffer_width.is_some() {
+            // Ways without a stroke of their own are filled with the document's.
+            let color = svg::Node::get_attributes(&path)
+                .and_then(|a| a.get("stroke"))
+                .map_or_else(|| "#000000".to_string(), |c| c.to_string());
+            path = path.set("fill", color).set("stroke", "none");
+        }
+        self.highlighting = outer_highlighting;
+        Some(path)
+    }
+
+    /// The way just drawn as `path`, split into stretches colored by how sharply they turn.
+    ///
+    /// Each segment takes the larger of the turns at its ends, and neighbouring segments of
+    /// the same shade are drawn as one path, in a group with the rest of the way's attributes.
+    fn curvature_group(&mut self, path: Path) -> Group {
+        let points = std::mem::take(&mut self.drawn_points);
+        let mut group = Group::new();
+        for (name, value) in svg::Node::get_attributes(&path).into_iter().flatten() {
+            if !matches!(name.as_str(), "d" | "stroke") {
+                group = group.set(name.as_str(), value.clone());
+            }
+        }
+        let angles = turning_angles(&points);
+        let shade = |i: usize| {
+            let turn = angles[i].max(angles[i + 1]) / (PI / 2.0);
+            (turn.min(1.0) * CURVATURE_STEPS).round()
+        };
+        let mut start = 0;
+        for end in 1..points.len() {
+            if end < points.len() - 1 && shade(end) == shade(start) {
+                continue;
+            }
+            let data = points[start + 1..=end]
+                .iter()
+                .fold(Data::new().move_to(points[start]), |d, p| d.line_to(*p));
+            let (straight, curvy) = CURVATURE_COLORS;
+            let color = straight.lerp(curvy, shade(start) / CURVATURE_STEPS);
+            group = group.add(Path::new().set("d", data).set("stroke", color.to_string()));
+            start = end;
+        }
+        group
+    }
+
+    /// A marker in the middle of a way's nodes, standing in for the whole way.
+    fn way_to_marker(&mut self, way: &Way) -> Option<Use> {
+        let nodes = way
+            .nodes
+            .iter()
+            .skip(1)
+            .filter_map(|n| self.nodes.node(*n))
+            .collect::<Vec<_>>();
+        if nodes.is_empty() {
+            return None;
+        }
+        let (mut x, mut y) = (0.0, 0.0);
+        for n in &nodes {
+            self.visit(n);
+            let p = self.project_node(n);
+            x += p.0 / nodes.len() as f64;
+            y += p.1 / nodes.len() as f64;
+        }
+        self.stats.ways += 1;
+        self.mark_rendered(OsmId::Way(way.id));
+        self.markers.insert("node-marker");
+        let marker = Use::new()
+            .set("href", "#node-marker")
+            .set("x", x)
+            .set("y", y);
+        Some(
+            self.set_stroke(marker, OsmId::Way(way.id), &way.tags)
+                .set("id", self.element_id(OsmId::Way(way.id), &way.tags)),
+        )
+    }
+
+    /// Projects a node, east of the antimeridian when unwrapping across it.
+    fn project_node(&self, node: &Node) -> (f64, f64) {
+        let (x, y) = self.project(node.lat(), node.lon());
+        if self.unwrap && node.lon() < 0.0 {
+            (x + 2.0 * PI * SCALE, y)
+        } else {
+            (x, y)
+        }
+    }
+
+    /// Projects latitude and longitude in degrees, shifted by `--offset-x` and `--offset-y`.
+    fn project(&self, lat: f64, lon: f64) -> (f64, f64) {
+        let (x, y) = self.projection.project(lat.to_radians(), lon.to_radians());
+        (x + self.args.offset_x, y + self.args.offset_y)
+    }
+
+    /// A miniature of everything drawn in the top right corner of the view box,
+    /// with the part shown in `window` outlined.
+    fn overview(
+        &self,
+        full: &Bound,
+        (x, y, width, height): (f64, f64, f64, f64),
+        (wx, wy, ww, wh): (f64, f64, f64, f64),
+    ) -> SVG {
+        let (mut fx, mut fy) = self.project(full.lat.end, full.lon.start);
+        let lower_right = self.project(full.lat.start, full.lon.end);
+        let (mut fw, mut fh) = (lower_right.0 - fx, lower_right.1 - fy);
+        if fw == 0.0 {
+            fx -= POINT_MARGIN;
+            fw = 2.0 * POINT_MARGIN;
+        }
+        if fh == 0.0 {
+            fy -= POINT_MARGIN;
+            fh = 2.0 * POINT_MARGIN;
+        }
+        // At most a quarter of the view box either way.
+        let scale = (width / 4.0 / fw).min(height / 4.0 / fh);
+        let stroke_width = self.args.stroke_width / scale;
+        let margin = width.min(height) / 40.0;
+        let rect = |x: f64, y: f64, width: f64, height: f64| {
+            Rectangle::new()
+                .set("x", x)
+                .set("y", y)
+                .set("width", width)
+                .set("height", height)
+                .set("stroke-width", stroke_width)
+        };
+        SVG::new()
+            .set("id", "overview")
+            .set("x", x + width - fw * scale - margin)
+            .set("y", y + margin)
+            .set("width", fw * scale)
+            .set("height", fh * scale)
+            .set("viewBox", (fx, fy, fw, fh))
+            .add(rect(fx, fy, fw, fh).set("fill", "#ffffff"))
+            .add(Use::new().set("href", "#overview-source"))
+            .add(
+                rect(wx, wy, ww, wh)
+                    .set("fill", "none")
+                    .set("stroke", self.args.highlight_color.to_string()),
+            )
+            .add(rect(fx, fy, fw, fh).set("fill", "none"))
+    }
+
+    /// The middle of a bound, in SVG coordinates.
+    fn center(&self, bound: &Bound) -> (f64, f64) {
+        let upper_left = self.project(bound.lat.end, bound.lon.start);
+        let lower_right = self.project(bound.lat.start, bound.lon.end);
+        (
+            (upper_left.0 + lower_right.0) / 2.0,
+            (upper_left.1 + lower_right.1) / 2.0,
+        )
+    }
+
+    /// A bound in the longitudes it is drawn at.
+    fn drawn(&self, bound: &Bound) -> Bound {
+        if self.unwrap {
+            bound.unwrapped()
+        } else {
+            bound.clone()
+        }
+    }
+
+    /// Accounts for a way or relation being drawn.
+    fn mark_rendered(&mut self, id: OsmId) {
+        self.rendered.insert(id);
+        self.note_edit(id);
+    }
+
+    /// Keeps track of the newest edit drawn, for `--data-date`.
+    fn note_edit(&mut self, id: OsmId) {
+        if let Some(&time) = self.edit_times.get(&id) {
+            self.newest_edit = self.newest_edit.max(Some(time));
+        }
+    }
+
+    /// Accounts for a node being drawn.
+    fn visit(&mut self, node: &Node) {
+        self.bound.update(node);
+        self.note_edit(OsmId::Node(node.id));
+        if self.args.reproject_only {
+            self.projected.insert(node.id, self.project_node(node));
+        }
+        if self.args.hull {
+            self.hull_points.push(self.project_node(node));
+        }
+        if self.args.relation_outline.is_some() {
+            self.outline_points.push(self.project_node(node));
+        }
+    }
+
+    /// Reports a problem with the data on stderr.
+    /// Reports a problem, counting references to missing objects towards `--max-warnings`.
+    fn warn(&self, kind: &str, id: i64, message: String) {
+        if kind.starts_with("missing_") {
+            let count = self.missing_warnings.get() + 1;
+            self.missing_warnings.set(count);
+            if self.args.max_warnings.is_some_and(|max| count > max) {
+                return;
+            }
+        }
+        warn(self.args.log_format, kind, id, message);
+    }
+
+    /// Reports how many missing objects went unreported because of `--max-warnings`.
+    fn warn_about_rest(&self) {
+        let Some(max) = self.args.max_warnings else {
+            return;
+        };
+        let more = self.missing_warnings.get().saturating_sub(max);
+        if more > 0 {
+            let message = format!("... and {more} more not found");
+            warn(self.args.log_format, "more_missing", more as i64, message);
+        }
+    }
+
+    /// A single filled path made of the given rings, with holes where rings overlap.
+    fn rings_to_path(&mut self, rings: &[Ring], tags: &Tags) -> Path {
+        let mut data = Data::new();
+        for ring in rings {
+            let nodes = ring
+                .nodes
+                .iter()
+                .filter_map(|n| self.nodes.node(*n))
+                .collect::<Vec<_>>();
+            let mut points = nodes
+                .iter()
+                .map(|n| self.project_node(n))
+                .collect::<Vec<_>>();
+            for n in &nodes {
+                self.visit(n);
+            }
+            if let Some(tolerance) = self.tolerance {
+                points = self.args.simplify_algo.simplify(points, tolerance);
+            }
+            for (i, p) in points.into_iter().enumerate() {
+                data = if i == 0 {
+                    data.move_to(p)
+                } else {
+                    data.line_to(p)
+                };
+            }
+            data = data.close();
+        }
+        Path::new()
+            .set("fill", fill_color(tags))
+            .set("fill-rule", "evenodd")
+            .set("d", data)
+    }
+
+    /// Simplifies the points of a way, halving the tolerance until `--simplify-min-points` are left.
+    ///
+    /// `positions` are where each of the way's `nodes` are among its points.
+    fn simplify_way(
+        &mut self,
+        nodes: &[Node],
+        positions: &[usize],
+        points: Vec<(f64, f64)>,
+        mut tolerance: f64,
+    ) -> Vec<(f64, f64)> {
+        let min_points = self.args.simplify_min_points.unwrap_or(0).min(points.len());
+        // Collinear points are dropped at any tolerance, so give up eventually.
+        for _ in 0..64 {
+            let simplified = if self.args.simplify_preserve_topology {
+                self.simplify_shared(nodes, positions, points.clone(), tolerance)
+            } else if self.args.simplify_keep_shared {
+                self.simplify_keeping_shared(nodes, positions, points.clone(), tolerance)
+            } else {
+                self.args.simplify_algo.simplify(points.clone(), tolerance)
+            };
+            if simplified.len() >= min_points {
+                return simplified;
+            }
+            tolerance /= 2.0;
+        }
+        points
+    }
+
+    /// Simplifies each chain of nodes between junctions separately, in a canonical direction,
+    /// so that an edge shared by several ways simplifies the same way in all of them.
+    fn simplify_shared(
+        &mut self,
+        nodes: &[Node],
+        positions: &[usize],
+        points: Vec<(f64, f64)>,
+        tolerance: f64,
+    ) -> Vec<(f64, f64)> {
+        if points.len() < 3 {
+            return points;
+        }
+        let algo = self.args.simplify_algo;
+        let mut keep = vec![false; points.len()];
+        let mut start = 0;
+        for end in 1..nodes.len() {
+            if end < nodes.len() - 1 && !self.junctions.contains(&nodes[end].id) {
+                continue;
+            }
+            let mut chain = nodes[start..=end].iter().map(|n| n.id).collect::<Vec<_>>();
+            let reversed = chain.last() < chain.first();
+            if reversed {
+                chain.reverse();
+            }
+            let (from, to) = (positions[start], positions[end]);
+            let mask = self.chains.entry(chain).or_insert_with(|| {
+                let mut chain_points = points[from..=to].to_vec();
+                if reversed {
+                    chain_points.reverse();
+                }
+                algo.mask(&chain_points, tolerance)
+            });
+            for (i, &k) in mask.iter().enumerate() {
+                keep[if reversed { to - i } else { from + i }] |= k;
+            }
+            start = end;
+        }
+        points
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(p, keep)| keep.then_some(p))
+            .collect()
+    }
+
+    /// Simplifies the stretches of a way between nodes shared with other ways separately,
+    /// so that the shared nodes are kept.
+    fn simplify_keeping_shared(
+        &self,
+        nodes: &[Node],
+        positions: &[usize],
+        points: Vec<(f64, f64)>,
+        tolerance: f64,
+    ) -> Vec<(f64, f64)> {
+        let mut keep = vec![false; points.len()];
+        let mut start = 0;
+        for end in 1..nodes.len() {
+            if end < nodes.len() - 1 && !self.shared_nodes.contains(&nodes[end].id) {
+                continue;
+            }
+            let (from, to) = (positions[start], positions[end]);
+            let mask = self.args.simplify_algo.mask(&points[from..=to], tolerance);
+            for (i, k) in mask.into_iter().enumerate() {
+                keep[from + i] |= k;
+            }
+            start = end;
+        }
+        points
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(p, keep)| keep.then_some(p))
+            .collect()
+    }
+
+    /// The explicit drawing order of a feature from its `z_order` or `z-index` tag.
+    ///
+    /// Features are drawn in ascending order, so higher ones end up on top;
+    /// features without the tag are at 0 and keep their relative order.
+    fn z_order(&self, id: &OsmId) -> i64 {
+        self.objs
+            .get(id)
+            .and_then(|o| {
+                let tags = o.tags();
+                tags.get("z_order").or_else(|| tags.get("z-index"))
+            })
+            .and_then(|z| z.trim().parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// A drawn feature, as a link to its page on openstreetmap.org with `--link-osm`.
+    fn link(&self, id: OsmId, node: impl Into<Box<dyn svg::Node>>) -> Box<dyn svg::Node> {
+        if !self.args.link_osm {
+            return node.into();
+        }
+        let href = format!(
+            "https://www.openstreetmap.org/{}/{}",
+            kind(id),
+            id.inner_id()
+        );
+        Anchor::new().set("href", href).add(node).into()
+    }
+
+    /// The SVG id of a way or relation: its OSM id, or with `--id-from` the value of that tag,
+    /// followed by its OSM id if another feature got that value first.
+    fn element_id(&mut self, id: OsmId, tags: &Tags) -> String {
+        match self
+            .args
+            .id_from
+            .as_ref()
+            .and_then(|key| tags.get(key.as_str()))
+        {
+            Some(value) => unique_id(&mut self.tag_ids, svg_id(value), id.inner_id()),
+            None => id.inner_id().to_string(),
+        }
+    }
+
+    /// Whether a way or node is left out by `--exclude-tag`.
+    fn excluded(&self, tags: &Tags) -> bool {
+        self.args.exclude_tag.iter().any(|f| f.matches(tags))
+    }
+
+    /// Puts features in the order they are drawn in, by z-order and with `--order-by-area`.
+    fn sort_features(&self, features: &mut [OsmId]) {
+        features.sort_by_key(|id| self.z_order(id));
+        if !self.args.order_by_area {
+            return;
+        }
+        // Only the filled ways trade places, so everything else stays where it was among them.
+        let areas = features
+            .iter()
+            .enumerate()
+            .filter_map(|(i, id)| Some((i, *id, self.filled_area(id)?)))
+            .collect::<Vec<_>>();
+        let mut sorted = areas
+            .iter()
+            .map(|&(_, id, area)| (id, area))
+            .collect::<Vec<_>>();
+        sorted.sort_by(|(a, a_area), (b, b_area)| {
+            self.z_order(a)
+                .cmp(&self.z_order(b))
+                .then(b_area.total_cmp(a_area))
+        });
+        for (&(i, _, _), (id, _)) in areas.iter().zip(sorted) {
+            features[i] = id;
+        }
+    }
+
+    /// The projected area of a way that `--fill` fills, or `None` if it isn't filled.
+    fn filled_area(&self, id: &OsmId) -> Option<f64> {
+        let way = self.objs.get(id)?.way()?;
+        (self.args.fill && way.is_closed() && is_area(&way.tags))
+            .then(|| polygon_area(self.nodes, way, &*self.projection))
+    }
+
+    fn set_stroke<N: svg::Node>(&self, mut node: N, id: OsmId, tags: &Tags) -> N {
+        // In a heatmap everything is drawn alike, so that only where features overlap stands out.
+        if self.args.heatmap {
+            return node;
+        }
+        if let (true, OsmId::Relation(rel)) = (self.args.auto_color, id) {
+            node.assign(
+                "stroke",
+                auto_color(rel.0, self.args.color_seed).to_string(),
+            )
+        }
+        if let Some(color) = osmc_color(tags) {
+            node.assign("stroke", color.to_string())
+        } else if let Some(color) = tags
+            .get("colour")
+            .or_else(|| tags.get("color"))
+            .filter(|s| s.starts_with('#'))
+        {
+            node.assign("stroke", color.to_string())
+        };
+        if let Some(color) = self.member_colors.get(&id) {
+            node.assign("stroke", color.clone())
+        }
+        if let Some(color) = self.role_color {
+            node.assign("stroke", color.to_string())
+        }
+        if let Some(change) = id.way().and_then(|w| self.changes.get(&w)) {
+            node.assign("stroke", change.color().to_string())
+        }
+        if let (Some(range), Some(&ele)) = (&self.ele_range, self.eles.get(&id)) {
+            let t = if range.end > range.start {
+                (ele - range.start) / (range.end - range.start)
+            } else {
+                0.0
+            };
+            let color = self.args.ele_low.lerp(self.args.ele_high, t);
+            node.assign("stroke", color.to_string())
+        }
+        if self.args.width_tag {
+            let lat = id
+                .way()
+                .and_then(|w| self.objs.get(&OsmId::Way(w))?.way()?.nodes.first().copied())
+                .and_then(|n| Some(self.nodes.lat_lon(n)?.0));
+            if let (Some(width), Some(lat)) = (tag_width(tags), lat) {
+                let width = width.clamp(self.args.width_min, self.args.width_max);
+                node.assign("stroke-width", meters_to_projected(width, lat));
+            }
+        }
+        if let Some(dashes) = surface_dashes(tags, self.args.stroke_width) {
+            node.assign("stroke-dasharray", dashes);
+        }
+        if let (true, OsmId::Way(way)) = (self.args.debug_colors, id) {
+            let color = DEBUG_PALETTE[way.0.rem_euclid(DEBUG_PALETTE.len() as i64) as usize];
+            node.assign("stroke", color.to_string());
+        }
+        if self.highlighting {
+            node.assign("stroke", self.args.highlight_color.to_string());
+            node.assign("stroke-width", 3.0 * self.args.stroke_width);
+        }
+        node
+    }
+}
+
+/// A color for a relation picked by hashing its id with `seed`, so it is the same every run.
+fn auto_color(id: i64, seed: u64) -> Rgb {
+    // SplitMix64, to spread consecutive ids over the color wheel.
+    const GOLDEN: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut z = (id as u64)
+        .wrapping_add(seed.wrapping_mul(GOLDEN))
+        .wrapping_add(GOLDEN);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    let hue = (z % 360) as f64;
+    // A fully saturated color of that hue, darkened a little to stand out on white.
+    let channel = |n: f64| {
+        let k = (n + hue / 60.0) % 6.0;
+        (200.0 * (1.0 - k.min(4.0 - k).clamp(0.0, 1.0))).round() as u8
+    };
+    Rgb(channel(5.0), channel(3.0), channel(1.0))
+}
+
+/// The `width` tag in meters, with or without a unit.
+fn tag_width(tags: &Tags) -> Option<f64> {
+    let width = tags.get("width")?;
+    width.trim_end_matches('m').trim().parse().ok()
+}
+
+/// The dash pattern for an unpaved `surface`: dashes for gravel, dots for dirt and ground.
+fn surface_dashes(tags: &Tags, stroke_width: f64) -> Option<String> {
+    match tags.get("surface")?.as_str() {
+        "gravel" => Some(format!("{} {}", 3.0 * stroke_width, 2.0 * stroke_width)),
+        // With round caps, zero-length dashes are drawn as dots.
+        "dirt" | "ground" => Some(format!("0 {}", 2.0 * stroke_width)),
+        _ => None,
+    }
+}
+
+/// A legend in the top left corner at `x`, `y` showing the `--ele-color` ramp over `range`.
+fn ramp_legend(range: &Range<f64>, x: f64, y: f64) -> Group {
+    let label = |text: String, x: f64, anchor: &str| {
+        Text::new(text)
+            .set("x", x)
+            .set("y", y + 3.0 * FONT_SIZE)
+            .set("font-size", FONT_SIZE)
+            .set("text-anchor", anchor)
+            .set("fill", "#000000")
+    };
+    let (left, width) = (x + FONT_SIZE, 10.0 * FONT_SIZE);
+    Group::new()
+        .set("id", "legend")
+        .set("stroke", "none")
+        .add(
+            Rectangle::new()
+                .set("x", left)
+                .set("y", y + FONT_SIZE)
+                .set("width", width)
+                .set("height", FONT_SIZE)
+                .set("fill", "url(#legend-ramp)"),
+        )
+        .add(label(range.start.to_string(), left, "start"))
+        .add(label(range.end.to_string(), left + width, "end"))
+}
+
+/// A TopoJSON topology of the features, keeping OSM ids and tags.
+fn topojson(
+    objs: &BTreeMap<OsmId, OsmObj>,
+    nodes: &impl NodeSource,
+    relations: &BTreeSet<RelationId>,
+    ways: &BTreeSet<WayId>,
+) -> serde_json::Value {
+    let mut topology = Topology {
+        objs,
+        nodes,
+        junctions: junctions(objs),
+        arcs: Vec::new(),
+        arc_ids: BTreeMap::new(),
+    };
+    let geometries = relations
+        .iter()
+        .map(|r| OsmId::Relation(*r))
+        .chain(ways.iter().map(|w| OsmId::Way(*w)))
+        .filter_map(|id| topology.geometry(id))
+        .collect::<Vec<_>>();
+    json!({
+        "type": "Topology",
+        "objects": { "collection": { "type": "GeometryCollection", "geometries": geometries } },
+        "arcs": topology.arcs,
+    })
+}
+
+/// The arcs of a TopoJSON topology, split at the same junctions as shared-edge simplification.
+struct Topology<'a, S: NodeSource> {
+    objs: &'a BTreeMap<OsmId, OsmObj>,
+    /// Where the coordinates of the nodes come from.
+    nodes: &'a S,
+    junctions: BTreeSet<NodeId>,
+    arcs: Vec<Vec<(f64, f64)>>,
+    /// The index of each arc, by its nodes in their canonical direction.
+    arc_ids: BTreeMap<Vec<NodeId>, usize>,
+}
+impl<S: NodeSource> Topology<'_, S> {
+    /// The TopoJSON geometry object of a feature, or `None` if it is missing.
+    fn geometry(&mut self, id: OsmId) -> Option<serde_json::Value> {
+        let mut geometry = match self.objs.get(&id)? {
+            OsmObj::Node(node) => {
+                json!({ "type": "Point", "coordinates": [node.lon(), node.lat()] })
+            }
+            OsmObj::Way(way) if way.is_closed() && is_area(&way.tags) => {
+                json!({ "type": "Polygon", "arcs": [self.arcs(&way.nodes)] })
+            }
+            OsmObj::Way(way) => json!({ "type": "LineString", "arcs": self.arcs(&way.nodes) }),
+            OsmObj::Relation(rel) if is_area_relation(rel) => {
+                // TopoJSON stays in degrees, so only the rings' nodes are used, not their points.
+                let mut rings = assemble_rings(self.objs, self.nodes, rel, &Equirectangular);
+                // The outer ring comes first, taking the largest if there are several.
+                rings.sort_by(|a, b| {
+                    (a.role == RingRole::Inner)
+                        .cmp(&(b.role == RingRole::Inner))
+                        .then(self.ring_area(b).total_cmp(&self.ring_area(a)))
+                });
+                let arcs = rings
+                    .iter()
+                    .map(|ring| self.arcs(&ring.nodes))
+                    .collect::<Vec<_>>();
+                json!({ "type": "Polygon", "arcs": arcs })
+            }
+            OsmObj::Relation(rel) => {
+                let geometries = rel
+                    .refs
+                    .iter()
+                    .filter_map(|r| self.geometry(r.member))
+                    .collect::<Vec<_>>();
+                json!({ "type": "GeometryCollection", "geometries": geometries })
+            }
+        };
+        let tags = self.objs[&id].tags();
+        geometry["id"] = format!("{}/{}", kind(id), id.inner_id()).into();
+        geometry["properties"] = tags
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string().into()))
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+        Some(geometry)
+    }
+
+    /// The indexes of the arcs making up a line of nodes, negated bitwise where reversed.
+    fn arcs(&mut self, nodes: &[NodeId]) -> Vec<i64> {
+        let mut nodes = nodes
+            .iter()
+            .filter_map(|n| self.nodes.node(*n))
+            .collect::<Vec<_>>();
+        nodes.dedup_by_key(|n| n.id);
+        let mut arcs = Vec::new();
+        let mut start = 0;
+        for end in 1..nodes.len() {
+            if end < nodes.len() - 1 && !self.junctions.contains(&nodes[end].id) {
+                continue;
+            }
+            let mut chain = nodes[start..=end].to_vec();
+            let reversed = chain.last().map(|n| n.id) < chain.first().map(|n| n.id);
+            if reversed {
+                chain.reverse();
+            }
+            let ids = chain.iter().map(|n| n.id).collect();
+            let index = *self.arc_ids.entry(ids).or_insert_with(|| {
+                self.arcs
+                    .push(chain.iter().map(|n| (n.lon(), n.lat())).collect());
+                self.arcs.len() - 1
+            });
+            arcs.push(if reversed {
+                !(index as i64)
+            } else {
+                index as i64
+            });
+            start = end;
+        }
+        arcs
+    }
+
+    /// The area of a ring in square degrees, only for comparing rings.
+    fn ring_area(&self, ring: &Ring) -> f64 {
+        let points = ring
+            .nodes
+            .iter()
+            .filter_map(|n| self.nodes.node(*n))
+            .map(|n| (n.lon(), n.lat()))
+            .collect::<Vec<_>>();
+        let twice_area: f64 = points
+            .windows(2)
+            .map(|w| w[0].0 * w[1].1 - w[1].0 * w[0].1)
+            .sum();
+        twice_area.abs() / 2.0
+    }
+}
+
+/// The WKT geometry of a way or relation, or `None` if it is missing.
+///
+/// Area relations become a `MULTIPOLYGON` with each inner ring in the outer ring containing it,
+/// other relations a `MULTILINESTRING` of all the ways in them.
+fn wkt(objs: &BTreeMap<OsmId, OsmObj>, nodes: &impl NodeSource, id: OsmId) -> Option<String> {
+    let points = |ids: &[NodeId]| {
+        ids.iter()
+            .filter_map(|n| nodes.node(*n))
+            .map(|n| (n.lon(), n.lat()))
+            .collect::<Vec<_>>()
+    };
+    let geometry = match objs.get(&id)? {
+        OsmObj::Way(way) if way.is_closed() && is_area(&way.tags) => {
+            format!("POLYGON {}", wkt_list(&[wkt_points(&points(&way.nodes))]))
+        }
+        OsmObj::Way(way) => format!("LINESTRING {}", wkt_points(&points(&way.nodes))),
+        OsmObj::Relation(rel) if is_area_relation(rel) => {
+            let (outers, inners): (Vec<_>, Vec<_>) =
+                assemble_rings(objs, nodes, rel, &Equirectangular)
+                    .into_iter()
+                    .map(|ring| (ring.role, points(&ring.nodes)))
+                    .partition(|(role, _)| *role == RingRole::Outer);
+            let mut polygons = outers
+                .into_iter()
+                .map(|(_, ring)| vec![ring])
+                .collect::<Vec<_>>();
+            for (_, inner) in inners {
+                let Some(&first) = inner.first() else {
+                    continue;
+                };
+                if let Some(polygon) = polygons
+                    .iter_mut()
+                    .find(|polygon| ring_contains(&polygon[0], first))
+                {
+                    polygon.push(inner);
+                }
+            }
+            let polygons = polygons
+                .iter()
+                .map(|rings| wkt_list(&rings.iter().map(|r| wkt_points(r)).collect::<Vec<_>>()))
+                .collect::<Vec<_>>();
+            format!("MULTIPOLYGON {}", wkt_list(&polygons))
+        }
+        OsmObj::Relation(rel) => {
+            let mut lines = Vec::new();
+            let mut stack = vec![rel];
+            let mut seen = BTreeSet::new();
+            while let Some(rel) = stack.pop() {
+                if !seen.insert(rel.id) {
+                    continue;
+                }
+                for r in &rel.refs {
+                    match objs.get(&r.member) {
+                        Some(OsmObj::Way(way)) if way.nodes.len() >= 2 => {
+                            lines.push(wkt_points(&points(&way.nodes)))
+                        }
+                        Some(OsmObj::Relation(child)) => stack.push(child),
+                        _ => {}
+                    }
+                }
+            }
+            format!("MULTILINESTRING {}", wkt_list(&lines))
+        }
+        OsmObj::Node(_) => return None,
+    };
+    Some(geometry)
+}
+
+/// A parenthesized WKT list of coordinates, or `EMPTY`.
+fn wkt_points(points: &[(f64, f64)]) -> String {
+    wkt_list(
+        &points
+            .iter()
+            .map(|(lon, lat)| format!("{lon} {lat}"))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// A parenthesized WKT list, or `EMPTY`.
+fn wkt_list(items: &[String]) -> String {
+    if items.is_empty() {
+        "EMPTY".to_string()
+    } else {
+        format!("({})", items.join(", "))
+    }
+}
+
+/// A style embedding a font file and setting all text in it, named after the file.
+fn font_style(path: &std::path::Path) -> Result<Style, Box<dyn Error>> {
+    let data = std::fs::read(path)?;
+    let family = path.file_stem().unwrap_or_default().to_string_lossy();
+    let (mime, format) = match path.extension().and_then(|e| e.to_str()) {
+        Some("otf") => ("font/otf", "opentype"),
+        Some("woff") => ("font/woff", "woff"),
+        Some("woff2") => ("font/woff2", "woff2"),
+        _ => ("font/ttf", "truetype"),
+    };
+    let data = base64::engine::general_purpose::STANDARD.encode(data);
+    Ok(Style::new(format!(
+        "@font-face {{ font-family: \"{family}\"; src: url(data:{mime};base64,{data}) format(\"{format}\"); }}\n\
+         text {{ font-family: \"{family}\"; }}"
+    )))
+}
+
+/// A scale bar in the bottom left corner of the view box, about a quarter of its width,
+/// measured at latitude `lat`.
+fn scale_bar(
+    (x, y, width, height): (f64, f64, f64, f64),
+    lat: f64,
+    units: DistanceUnit,
+    stroke_width: f64,
+) -> Group {
+    let (meters, name) = match units {
+        DistanceUnit::Km => (1000.0, "km"),
+        DistanceUnit::Mi => (1609.344, "mi"),
+    };
+    let unit_length = meters_to_projected(meters, lat);
+    // The largest of 1, 2 or 5 times a power of ten that fits.
+    let target = width / 4.0 / unit_length;
+    let power = 10f64.powf(target.log10().floor());
+    let distance = [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|m| m * power)
+        .find(|&d| d <= target)
+        .unwrap_or(power);
+    let length = distance * unit_length;
+    let (left, bottom) = (x + FONT_SIZE, y + height - FONT_SIZE);
+    let tick = FONT_SIZE / 2.0;
+    let data = Data::new()
+        .move_to((left, bottom - tick))
+        .line_to((left, bottom))
+        .line_to((left + length, bottom))
+        .line_to((left + length, bottom - tick));
+    Group::new()
+        .set("id", "scale-bar")
+        .add(
+            Path::new()
+                .set("d", data)
+                .set("fill", "none")
+                .set("stroke", "#000000")
+                .set("stroke-width", stroke_width),
+        )
+        .add(
+            Text::new(format!("{distance} {name}"))
+                .set("x", left + length / 2.0)
+                .set("y", bottom - tick)
+                .set("font-size", FONT_SIZE)
+                .set("text-anchor", "middle")
+                .set("stroke", "none")
+                .set("fill", "#000000"),
+        )
+}
+
+/// Lines of latitude and longitude every `spacing` degrees across `bound`, or how many there
+/// would be if that's more than [`MAX_GRATICULE_LINES`].
+fn graticule(
+    bound: &Bound,
+    spacing: f64,
+    stroke_width: f64,
+    project: &dyn Fn(f64, f64) -> (f64, f64),
+) -> Result<Group, usize> {
+    // Enough points per line for it to follow the curve of projections other than Mercator.
+    const STEPS: usize = 16;
+    let multiples = |range: &Range<f64>| {
+        // Lines right on the edge of the bound shouldn't get lost to rounding.
+        let first = (range.start / spacing - 1e-9).ceil() as i64;
+        let last = (range.end / spacing + 1e-9).floor() as i64;
+        first..=last
+    };
+    let (lats, lons) = (multiples(&bound.lat), multiples(&bound.lon));
+    // Saturating, as a spacing small enough saturates the multiples themselves.
+    let count = |range: &RangeInclusive<i64>| {
+        range
+            .end()
+            .saturating_sub(*range.start())
+            .saturating_add(1)
+            .max(0) as u64
+    };
+    let lines = count(&lats).saturating_add(count(&lons));
+    if lines > MAX_GRATICULE_LINES as u64 {
+        return Err(lines.try_into().unwrap_or(usize::MAX));
+    }
+    let steps = |range: &Range<f64>| {
+        (0..=STEPS)
+            .map(|i| range.start + (range.end - range.start) * i as f64 / STEPS as f64)
+            .collect::<Vec<_>>()
+    };
+
+    let mut group = Group::new()
+        .set("id", "graticule")
+        .set("stroke", "#cccccc")
+        .set("stroke-width", stroke_width / 2.0)
+        .set("fill", "none");
+    for lat in lats.map(|i| i as f64 * spacing) {
+        group = group.add(degree_line(
+            steps(&bound.lon).into_iter().map(|lon| (lat, lon)),
+            project,
+        ));
+    }
+    for lon in lons.map(|i| i as f64 * spacing) {
+        group = group.add(degree_line(
+            steps(&bound.lat).into_iter().map(|lat| (lat, lon)),
+            project,
+        ));
+    }
+    Ok(group)
+}
+
+/// A path through points given as latitude and longitude in degrees, projected by `project`.
+fn degree_line(
+    points: impl IntoIterator<Item = (f64, f64)>,
+    project: &dyn Fn(f64, f64) -> (f64, f64),
+) -> Path {
+    let mut data = Data::new();
+    for (i, (lat, lon)) in points.into_iter().enumerate() {
+        let p = project(lat, lon);
+        data = if i == 0 {
+            data.move_to(p)
+        } else {
+            data.line_to(p)
+        };
+    }
+    Path::new().set("d", data)
+}
+
+/// Adds evenly spaced points along segments spanning more than `max_degrees` of latitude or
+/// longitude, so that none does, along with where each of `nodes` ended up among them.
+///
+/// The added points aren't OSM nodes, so they have the id 0 of none and are only good for
+/// where they are.
+fn densify(nodes: &[Node], max_degrees: f64) -> (Vec<Node>, Vec<usize>) {
+    let mut dense = Vec::with_capacity(nodes.len());
+    let mut positions = Vec::with_capacity(nodes.len());
+    for pair in nodes.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        positions.push(dense.len());
+        dense.push(a.clone());
+        let span = (b.lat() - a.lat()).abs().max((b.lon() - a.lon()).abs());
+        let steps = (span / max_degrees).ceil() as i32;
+        for i in 1..steps {
+            let t = i as f64 / steps as f64;
+            let lerp = |from: i32, to: i32| (from as f64 + (to - from) as f64 * t).round() as i32;
+            dense.push(Node {
+                id: NodeId(0),
+                tags: Tags::new(),
+                decimicro_lat: lerp(a.decimicro_lat, b.decimicro_lat),
+                decimicro_lon: lerp(a.decimicro_lon, b.decimicro_lon),
+            });
+        }
+    }
+    positions.extend(nodes.last().map(|_| dense.len()));
+    dense.extend(nodes.last().cloned());
+    (dense, positions)
+}
+
+/// Joins runs of sibling paths that differ only in their id and data into one path each.
+///
+/// Filled paths are left alone, as their fill rule would apply across the joined rings.
+fn merge_paths(mut group: Group) -> Group {
+    let Some(children) = svg::Node::get_children_mut(&mut group) else {
+        return group;
+    };
+    let mut merged = Vec::<Box<dyn svg::Node>>::with_capacity(children.len());
+    for child in children.drain(..) {
+        match merged.last_mut() {
+            Some(last) if same_style(&**last, &*child) => {
+                let data = format!(
+                    "{} {}",
+                    last.get_attributes().unwrap()["d"],
+                    child.get_attributes().unwrap()["d"]
+                );
+                let attributes = last.get_attributes_mut().unwrap();
+                // The joined path stands for several ways, so carries the id of none of them.
+                attributes.remove("id");
+                attributes.insert("d".to_string(), data.into());
+            }
+            _ => merged.push(child),
+        }
+    }
+    *children = merged;
+    group
+}
+
+/// Whether two nodes are unfilled paths with the same attributes, besides their id and data.
+fn same_style(a: &dyn svg::Node, b: &dyn svg::Node) -> bool {
+    let style = |node: &dyn svg::Node| {
+        if node.get_name() != "path" || node.get_children().is_some_and(|c| !c.is_empty()) {
+            return None;
+        }
+        let attributes = node.get_attributes()?;
+        if attributes.get("fill").is_some_and(|f| *f != "none") {
+            return None;
+        }
+        let style = attributes
+            .iter()
+            .filter(|(name, _)| !matches!(name.as_str(), "id" | "d"))
+            .map(|(name, value)| (name.clone(), value.to_string()))
+            .collect::<BTreeMap<_, _>>();
+        Some(style)
+    };
+    style(a).is_some_and(|s| style(b) == Some(s))
+}
+
+/// Whether a closed way describes an area rather than a loop, following common tagging.
+fn is_area(tags: &Tags) -> bool {
+    match tags.get("area").map(|a| a.as_str()) {
+        Some("yes") => true,
+        Some("no") => false,
+        _ => ["building", "landuse", "leisure", "amenity"]
+            .iter()
+            .any(|k| tags.contains_key(*k)),
+    }
+}
+
+/// The id of the `--icons` icon standing for a node's tags, if there is one.
+fn icon(tags: &Tags) -> Option<&'static str> {
+    let tagged = |tag: &str| {
+        tag.split_once('=')
+            .is_some_and(|(key, value)| tags.get(key).is_some_and(|v| v == value))
+    };
+    ICONS
+        .iter()
+        .find(|(_, icon_tags, _)| icon_tags.iter().any(|t| tagged(t)))
+        .map(|(id, _, _)| *id)
+}
+
+/// The color of a waymarked trail from its `osmc:symbol`, e.g. `red:white:red_bar`.
+///
+/// The way color comes first; without a known one, the color of the foreground symbol is used.
+fn osmc_color(tags: &Tags) -> Option<Rgb> {
+    let symbol = tags.get("osmc:symbol")?;
+    let mut parts = symbol.split(':');
+    let way_color = parts.next();
+    // The foreground is a color with a shape, e.g. `red_bar`.
+    let foreground = parts.nth(1).and_then(|f| f.split('_').next());
+    way_color
+        .and_then(named_color)
+        .or_else(|| foreground.and_then(named_color))
+}
+
+/// The colors `osmc:symbol` names.
+fn named_color(name: &str) -> Option<Rgb> {
+    Some(match name {
+        "black" => Rgb(0x00, 0x00, 0x00),
+        "blue" => Rgb(0x00, 0x00, 0xff),
+        "brown" => Rgb(0x8b, 0x45, 0x13),
+        "gray" | "grey" => Rgb(0x80, 0x80, 0x80),
+        "green" => Rgb(0x00, 0x80, 0x00),
+        "orange" => Rgb(0xff, 0xa5, 0x00),
+        "purple" => Rgb(0x80, 0x00, 0x80),
+        "red" => Rgb(0xff, 0x00, 0x00),
+        "white" => Rgb(0xff, 0xff, 0xff),
+        "yellow" => Rgb(0xff, 0xff, 0x00),
+        _ => return None,
+    })
+}
+
+fn fill_color(tags: &Tags) -> String {
+    tags.get("colour")
+        .filter(|s| s.starts_with('#'))
+        .map_or_else(|| "#cccccc".to_string(), |c| c.to_string())
+}
+
+/// The nodes that are in more than one way.
+fn shared_nodes(objs: &BTreeMap<OsmId, OsmObj>) -> BTreeSet<NodeId> {
+    let mut seen = BTreeSet::new();
+    let mut shared = BTreeSet::new();
+    for way in objs.values().filter_map(OsmObj::way) {
+        // A closed way only meets itself at its ends.
+        let nodes = way.nodes.iter().copied().collect::<BTreeSet<_>>();
+        for n in nodes {
+            if !seen.insert(n) {
+                shared.insert(n);
+            }
+        }
+    }
+    shared
+}
+
+/// The nodes at which simplification must split ways to simplify shared edges consistently:
+/// the ends of ways, and shared nodes next to a node that isn't shared by the same ways.
+fn junctions(objs: &BTreeMap<OsmId, OsmObj>) -> BTreeSet<NodeId> {
+    let ways = objs.values().filter_map(OsmObj::way);
+    let mut ways_of = BTreeMap::<NodeId, BTreeSet<WayId>>::new();
+    for way in ways.clone() {
+        for n in &way.nodes {
+            ways_of.entry(*n).or_default().insert(way.id);
+        }
+    }
+    let mut junctions = BTreeSet::new();
+    for way in ways {
+        junctions.extend(way.nodes.first());
+        junctions.extend(way.nodes.last());
+        for pair in way.nodes.windows(2) {
+            if ways_of[&pair[0]] != ways_of[&pair[1]] {
+                junctions.extend(pair.iter().filter(|n| ways_of[*n].len() > 1));
+            }
+        }
+    }
+    junctions
+}
+
+/// The color of a `forward` or `backward` member of a route:
+/// a lighter shade of the route's color for forward, and lighter still for backward.
+fn direction_color(rel: &Relation, role: &str) -> Option<Rgb> {
+    if rel.tags.get("type").is_none_or(|t| t != "route") {
+        return None;
+    }
+    let base = rel
+        .tags
+        .get("colour")
+        .and_then(|c| parse_color(c).ok())
+        .unwrap_or(Rgb(0, 0, 0));
+    match role {
+        "forward" => Some(base.lerp(Rgb(255, 255, 255), 0.3)),
+        "backward" => Some(base.lerp(Rgb(255, 255, 255), 0.6)),
+        _ => None,
+    }
+}
+
+/// Whether the member order of a relation is significant, as for routes.
+fn is_ordered(rel: &Relation) -> bool {
+    rel.tags
+        .get("type")
+        .is_some_and(|t| matches!(t.as_str(), "route" | "route_master" | "superroute"))
+}
+
+fn ele(tags: &Tags) -> Option<f64> {
+    tags.get("ele").and_then(|e| e.trim().parse().ok())
+}
+
+/// The elevation of a way: its own `ele` tag, or else the mean of its nodes' `ele` tags.
+fn way_ele(nodes: &impl NodeSource, way: &Way) -> Option<f64> {
+    ele(&way.tags).or_else(|| {
+        let eles = way
+            .nodes
+            .iter()
+            .filter_map(|n| ele(&nodes.node(*n)?.tags))
+            .collect::<Vec<_>>();
+        (!eles.is_empty()).then(|| eles.iter().sum::<f64>() / eles.len() as f64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bound(lat: Range<f64>, lon: Range<f64>) -> Bound {
+        let lon_east = lon.start.rem_euclid(360.0)..lon.end.rem_euclid(360.0);
+        Bound { lat, lon, lon_east }
+    }
+
+    fn node(id: i64, lat: f64, lon: f64) -> Node {
+        Node {
+            id: NodeId(id),
+            tags: Tags::new(),
+            decimicro_lat: (lat * 1e7).round() as i32,
+            decimicro_lon: (lon * 1e7).round() as i32,
+        }
+    }
+
+    #[test]
+    fn densify_says_where_the_nodes_went() {
+        let nodes = [node(1, 0.0, 0.0), node(2, 0.0, 0.3), node(3, 0.05, 0.3)];
+        let (dense, positions) = densify(&nodes, 0.1);
+        assert_eq!(positions, [0, 3, 4]);
+        for (n, &i) in nodes.iter().zip(&positions) {
+            assert_eq!(dense[i].id, n.id);
+        }
+        assert_eq!(dense.len(), 5);
+        assert!((dense[1].lon() - 0.1).abs() < 1e-7);
+    }
+
+    #[test]
+    fn unique_id_adds_the_suffix_on_collision() {
+        let mut taken = BTreeSet::new();
+        assert_eq!(unique_id(&mut taken, svg_id("a b"), 1), "a_b");
+        assert_eq!(unique_id(&mut taken, svg_id("a_b"), 2), "a_b-2");
+        assert_eq!(unique_id(&mut taken, "a_b".to_string(), 2), "a_b-2-2");
+        assert_eq!(unique_id(&mut taken, layer_id("a b"), 3), "layer-a_b");
+    }
+
+    #[test]
+    fn svg_id_starts_like_an_id() {
+        assert_eq!(svg_id("A 7"), "A_7");
+        assert_eq!(svg_id("7"), "_7");
+        assert_eq!(svg_id("-x"), "_-x");
+    }
+
+    #[test]
+    fn graticule_draws_a_line_per_degree() {
+        let project = |lat: f64, lon: f64| (lon, -lat);
+        let group = graticule(&bound(10.5..12.5, 20.0..22.0), 1.0, 1.0, &project).unwrap();
+        // Latitudes 11 and 12, and longitudes 20, 21 and 22, on the edge.
+        assert_eq!(svg::Node::get_children(&group).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn graticule_refuses_too_many_lines() {
+        let project = |lat: f64, lon: f64| (lon, -lat);
+        assert!(graticule(&bound(0.0..1.0, 0.0..1.0), 0.001, 1.0, &project).is_err());
+        assert!(graticule(&bound(0.0..1.0, 0.0..1.0), 1e-300, 1.0, &project).is_err());
+    }
+
+    fn way(id: i64, nodes: &[i64], tags: &[(&str, &str)]) -> OsmObj {
+        OsmObj::Way(Way {
+            id: WayId(id),
+            tags: tags.iter().map(|&(k, v)| (k.into(), v.into())).collect(),
+            nodes: nodes.iter().map(|&n| NodeId(n)).collect(),
+        })
+    }
+
+    fn objects(objs: impl IntoIterator<Item = OsmObj>) -> BTreeMap<OsmId, OsmObj> {
+        objs.into_iter().map(|o| (o.id(), o)).collect()
+    }
+
+    fn relation(id: i64, members: &[(OsmId, &str)], tags: &[(&str, &str)]) -> OsmObj {
+        OsmObj::Relation(Relation {
+            id: RelationId(id),
+            tags: tags.iter().map(|&(k, v)| (k.into(), v.into())).collect(),
+            refs: members
+                .iter()
+                .map(|&(member, role)| osmpbfreader::Ref {
+                    member,
+                    role: role.into(),
+                })
+                .collect(),
+        })
+    }
+
+    /// The corners of a one-degree square, numbered anticlockwise from `first` at `(lat, lon)`.
+    fn square(first: i64, lat: f64, lon: f64) -> [OsmObj; 4] {
+        [
+            OsmObj::Node(node(first, lat, lon)),
+            OsmObj::Node(node(first + 1, lat, lon + 1.0)),
+            OsmObj::Node(node(first + 2, lat + 1.0, lon + 1.0)),
+            OsmObj::Node(node(first + 3, lat + 1.0, lon)),
+        ]
+    }
+
+    /// The start tag of the element with the given id.
+    fn element<'a>(svg: &'a str, id: &str) -> &'a str {
+        let at = svg.find(&format!(" id=\"{id}\"")).unwrap();
+        let start = svg[..at].rfind('<').unwrap();
+        &svg[start..start + svg[start..].find('>').unwrap() + 1]
+    }
+
+    /// The value of an attribute in a start tag.
+    fn attribute<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+        let value = element.split(&format!(" {name}=\"")).nth(1)?;
+        Some(&value[..value.find('"')?])
+    }
+
+    /// A path in the temporary directory that no other test run uses.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("osm-rels-svg-test-{}-{name}", std::process::id()))
+    }
+
+    /// Who last edited an object written by [`write_pbf`], and in which changeset.
+    #[derive(Clone, Copy)]
+    struct Edit {
+        user: &'static str,
+        changeset: i64,
+        timestamp: i64,
+    }
+
+    fn varint(out: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn zigzag(value: i64) -> u64 {
+        ((value << 1) ^ (value >> 63)) as u64
+    }
+
+    fn field(out: &mut Vec<u8>, number: u64, value: u64) {
+        varint(out, number << 3);
+        varint(out, value);
+    }
+
+    fn bytes_field(out: &mut Vec<u8>, number: u64, bytes: &[u8]) {
+        varint(out, number << 3 | 2);
+        varint(out, bytes.len() as u64);
+        out.extend_from_slice(bytes);
+    }
+
+    fn packed(out: &mut Vec<u8>, number: u64, values: impl IntoIterator<Item = u64>) {
+        let mut bytes = Vec::new();
+        for value in values {
+            varint(&mut bytes, value);
+        }
+        bytes_field(out, number, &bytes);
+    }
+
+    /// The differences between successive values, as PBF stores ids and coordinates.
+    fn deltas(values: impl IntoIterator<Item = i64>) -> Vec<u64> {
+        let mut previous = 0;
+        let mut deltas = Vec::new();
+        for value in values {
+            deltas.push(zigzag(value - previous));
+            previous = value;
+        }
+        deltas
+    }
+
+    fn string_id(strings: &mut Vec<String>, s: &str) -> u64 {
+        let id = strings.iter().position(|t| t == s).unwrap_or_else(|| {
+            strings.push(s.to_string());
+            strings.len() - 1
+        });
+        id as u64
+    }
+
+    fn blob(out: &mut Vec<u8>, kind: &str, data: &[u8]) {
+        let mut blob = Vec::new();
+        bytes_field(&mut blob, 1, data);
+        field(&mut blob, 2, data.len() as u64);
+        let mut header = Vec::new();
+        bytes_field(&mut header, 1, kind.as_bytes());
+        field(&mut header, 3, blob.len() as u64);
+        out.extend_from_slice(&(header.len() as u32).to_be_bytes());
+        out.extend(header);
+        out.extend(blob);
+    }
+
+    /// Writes the objects to a PBF extract in the temporary directory, in a single
+    /// uncompressed block, so that [`select`] can read them.
+    fn write_pbf(name: &str, objs: &[(OsmObj, Option<Edit>)]) -> std::path::PathBuf {
+        let mut strings = vec![String::new()];
+        let mut group = Vec::new();
+        for (obj, edit) in objs {
+            let mut message = Vec::new();
+            let id = obj.id().inner_id();
+            match obj {
+                OsmObj::Node(_) => field(&mut message, 1, zigzag(id)),
+                _ => field(&mut message, 1, id as u64),
+            }
+            let tags = obj.tags().iter().collect::<Vec<_>>();
+            if !tags.is_empty() {
+                let keys = tags.iter().map(|(k, _)| string_id(&mut strings, k));
+                packed(&mut message, 2, keys.collect::<Vec<_>>());
+                let values = tags.iter().map(|(_, v)| string_id(&mut strings, v));
+                packed(&mut message, 3, values.collect::<Vec<_>>());
+            }
+            if let Some(edit) = edit {
+                let mut info = Vec::new();
+                field(&mut info, 1, 1);
+                field(&mut info, 2, edit.timestamp as u64);
+                field(&mut info, 3, edit.changeset as u64);
+                field(&mut info, 5, string_id(&mut strings, edit.user));
+                bytes_field(&mut message, 4, &info);
+            }
+            let number = match obj {
+                OsmObj::Node(node) => {
+                    field(&mut message, 8, zigzag(node.decimicro_lat.into()));
+                    field(&mut message, 9, zigzag(node.decimicro_lon.into()));
+                    1
+                }
+                OsmObj::Way(way) => {
+                    packed(&mut message, 8, deltas(way.nodes.iter().map(|n| n.0)));
+                    3
+                }
+                OsmObj::Relation(rel) => {
+                    let roles = rel.refs.iter().map(|r| string_id(&mut strings, &r.role));
+                    packed(&mut message, 8, roles.collect::<Vec<_>>());
+                    packed(
+                        &mut message,
+                        9,
+                        deltas(rel.refs.iter().map(|r| r.member.inner_id())),
+                    );
+                    let types = rel.refs.iter().map(|r| match r.member {
+                        OsmId::Node(_) => 0,
+                        OsmId::Way(_) => 1,
+                        OsmId::Relation(_) => 2,
+                    });
+                    packed(&mut message, 10, types);
+                    4
+                }
+            };
+            bytes_field(&mut group, number, &message);
+        }
+        let mut table = Vec::new();
+        for s in &strings {
+            bytes_field(&mut table, 1, s.as_bytes());
+        }
+        let mut block = Vec::new();
+        bytes_field(&mut block, 1, &table);
+        bytes_field(&mut block, 2, &group);
+        let mut header = Vec::new();
+        bytes_field(&mut header, 4, b"OsmSchema-V0.6");
+        let mut pbf = Vec::new();
+        blob(&mut pbf, "OSMHeader", &header);
+        blob(&mut pbf, "OSMData", &block);
+        let path = temp_path(&format!("{name}.osm.pbf"));
+        std::fs::write(&path, pbf).unwrap();
+        path
+    }
+
+    /// Selects from the objects written to an extract, as `main` does with the given flags.
+    fn select_from(
+        name: &str,
+        objs: &[(OsmObj, Option<Edit>)],
+        flags: &[&str],
+        ways: &[i64],
+        relations: &[i64],
+    ) -> Result<Selection, RenderError> {
+        let path = write_pbf(name, objs);
+        let data = path.to_str().unwrap();
+        let mut args = Args::parse_from(["osm-rels-svg", "-d", data].iter().chain(flags));
+        let ways = ways.iter().map(|&w| WayId(w)).collect();
+        let relations = relations.iter().map(|&r| RelationId(r)).collect();
+        let selection = select(&mut args, ways, relations);
+        std::fs::remove_file(path).unwrap();
+        selection
+    }
+
+    /// Draws the given relations and ways with the given flags, as SVG.
+    fn draw(
+        objs: &BTreeMap<OsmId, OsmObj>,
+        flags: &[&str],
+        relations: &[i64],
+        ways: &[i64],
+    ) -> String {
+        let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf"].iter().chain(flags));
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let mut renderer = Renderer::new(objs, objs, &changes, &edit_times, &args, None);
+        let relations = relations.iter().map(|&r| RelationId(r)).collect();
+        let ways = ways.iter().map(|&w| WayId(w)).collect();
+        svg_string(&args, &renderer.render(&relations, &ways))
+    }
+
+    #[test]
+    fn lod_paths_carry_the_zoom() {
+        let path = lod_path(std::path::Path::new("out/map.svg"), 12);
+        assert_eq!(path, std::path::Path::new("out/map-z12.svg"));
+        let path = lod_path(std::path::Path::new("map"), 3);
+        assert_eq!(path, std::path::Path::new("map-z3.svg"));
+    }
+
+    #[test]
+    fn junctions_split_where_ways_part() {
+        // Ways 10 and 11 share 2 and 3, then part; 4 is only on 10, 5 only on 11.
+        let objs = objects([way(10, &[1, 2, 3, 4], &[]), way(11, &[2, 3, 5], &[])]);
+        assert_eq!(
+            junctions(&objs),
+            [1, 2, 3, 4, 5].map(NodeId).into_iter().collect()
+        );
+        let objs = objects([way(10, &[1, 2, 3, 4], &[]), way(11, &[6, 2, 3, 7], &[])]);
+        assert!(junctions(&objs).contains(&NodeId(2)));
+        assert!(junctions(&objs).contains(&NodeId(3)));
+    }
+
+    #[test]
+    fn shared_nodes_are_on_several_ways() {
+        let objs = objects([way(10, &[1, 2, 3, 1], &[]), way(11, &[3, 4], &[])]);
+        assert_eq!(shared_nodes(&objs), [NodeId(3)].into());
+    }
+
+    #[test]
+    fn shared_nodes_survive_simplification() {
+        let nodes = [node(1, 0.0, 0.0), node(2, 0.0001, 1.0), node(3, 0.0, 2.0)];
+        let objs = objects(
+            nodes
+                .iter()
+                .cloned()
+                .map(OsmObj::Node)
+                .chain([way(10, &[1, 2, 3], &[]), way(11, &[2, 4], &[])]),
+        );
+        let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf", "--simplify-keep-shared"]);
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, None);
+        let points = nodes
+            .iter()
+            .map(|n| renderer.project_node(n))
+            .collect::<Vec<_>>();
+        let tolerance = SCALE;
+        assert_eq!(
+            args.simplify_algo.simplify(points.clone(), tolerance).len(),
+            2
+        );
+        let kept = renderer.simplify_keeping_shared(&nodes, &[0, 1, 2], points.clone(), tolerance);
+        assert_eq!(kept, points);
+    }
+
+    #[test]
+    fn tag_filters_match_any_of_their_values() {
+        let filter = parse_tag_filter("highway=primary|secondary").unwrap();
+        let tags = |v: &str| [("highway".into(), v.into())].into_iter().collect::<Tags>();
+        assert!(filter.matches(&tags("secondary")));
+        assert!(!filter.matches(&tags("tertiary")));
+        assert!(parse_tag_filter("highway").is_err());
+    }
+
+    #[test]
+    fn bbox_is_lon_lat_and_ordered() {
+        let bbox = parse_bbox("10, 50, 11, 51").unwrap();
+        assert_eq!((bbox.lat, bbox.lon), (50.0..51.0, 10.0..11.0));
+        assert!(parse_bbox("11,50,10,51").is_err());
+        assert!(parse_bbox("10,50,11").is_err());
+        let inside = bound(50.5..50.6, 10.5..12.0);
+        assert!(parse_bbox("10,50,11,51").unwrap().intersects(&inside));
+        assert!(!parse_bbox("10,50,11,51")
+            .unwrap()
+            .intersects(&bound(52.0..53.0, 10.0..11.0)));
+    }
+
+    #[test]
+    fn bounds_across_the_antimeridian_are_narrow() {
+        let mut across = Bound::new();
+        across.update(&node(1, 0.0, 179.0));
+        across.update(&node(2, 1.0, -179.0));
+        assert!(across.crosses_antimeridian());
+        assert_eq!(across.wgs84(), (179.0, 0.0, -179.0, 1.0));
+        let mut along = Bound::new();
+        along.update(&node(1, 0.0, -1.0));
+        along.update(&node(2, 1.0, 1.0));
+        assert!(!along.crosses_antimeridian());
+        assert_eq!(along.wgs84(), (-1.0, 0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn wkt_tells_areas_from_lines() {
+        let objs = objects([
+            OsmObj::Node(node(1, 0.0, 0.0)),
+            OsmObj::Node(node(2, 0.0, 1.0)),
+            OsmObj::Node(node(3, 1.0, 1.0)),
+            way(10, &[1, 2, 3, 1], &[("building", "yes")]),
+            way(11, &[1, 2, 3], &[]),
+        ]);
+        assert_eq!(
+            wkt(&objs, &objs, OsmId::Way(WayId(10))).unwrap(),
+            "POLYGON ((0 0, 1 0, 1 1, 0 0))"
+        );
+        assert_eq!(
+            wkt(&objs, &objs, OsmId::Way(WayId(11))).unwrap(),
+            "LINESTRING (0 0, 1 0, 1 1)"
+        );
+        assert!(wkt(&objs, &objs, OsmId::Node(NodeId(1))).is_none());
+    }
+
+    #[test]
+    fn reach_is_the_farthest_edge() {
+        assert_eq!(reach([-5.0, 1.0, 2.0, 3.0]), 5.0);
+        assert_eq!(reach([1.0, 2.0, 3.0, 10.0]), 12.0);
+    }
+
+    #[test]
+    fn atomic_writes_leave_nothing_behind_on_failure() {
+        let dir = std::env::temp_dir().join(format!("osm-rels-svg-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.svg");
+        write_atomically(&path, b"first").unwrap();
+        let failed = write_atomically_with(&path, |out| {
+            out.write_all(b"partial")?;
+            Err(std::io::Error::other("interrupted"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"first");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn merge_paths_joins_only_unfilled_alike_paths() {
+        let path = |id: &str, d: &str, stroke: &str, fill: &str| {
+            Path::new()
+                .set("id", id)
+                .set("d", d)
+                .set("stroke", stroke)
+                .set("fill", fill)
+        };
+        let group = Group::new()
+            .add(path("1", "M0,0 L1,1", "#000000", "none"))
+            .add(path("2", "M1,1 L2,2", "#000000", "none"))
+            .add(path("3", "M2,2 L3,3", "#ff0000", "none"))
+            .add(path("4", "M0,0 L1,1 L1,0 Z", "#ff0000", "#00ff00"))
+            .add(path("5", "M0,0 L1,1 L1,0 Z", "#ff0000", "#00ff00"));
+        let group = merge_paths(group);
+        let children = svg::Node::get_children(&group).unwrap();
+        assert_eq!(children.len(), 4);
+        let merged = children[0].get_attributes().unwrap();
+        assert_eq!(merged["d"].to_string(), "M0,0 L1,1 M1,1 L2,2");
+        assert!(!merged.contains_key("id"));
+        assert_eq!(children[1].get_attributes().unwrap()["id"].to_string(), "3");
+    }
+
+    #[test]
+    fn iso_dates_count_leap_days() {
+        assert_eq!(iso_date(0), "1970-01-01");
+        assert_eq!(iso_date(-1), "1969-12-31");
+        assert_eq!(iso_date(951_782_400), "2000-02-29");
+        assert_eq!(iso_date(1_709_251_199), "2024-02-29");
+        assert_eq!(iso_date(4_107_542_400), "2100-03-01");
+    }
+
+    #[test]
+    fn ring_ways_of_left_out_relations_are_still_drawn() {
+        let way_id = OsmId::Way(WayId(10));
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            way(10, &[1, 2, 3, 4, 1], &[]),
+            relation(
+                100,
+                &[(way_id, "outer"), (OsmId::Way(WayId(11)), "outer")],
+                &[("type", "multipolygon")],
+            ),
+        ]));
+        let svg = draw(&objs, &["--fill"], &[100], &[10]);
+        assert_eq!(svg.matches("<path").count(), 1);
+        let svg = draw(&objs, &["--fill", "--require-complete"], &[100], &[10]);
+        assert_eq!(svg.matches("<path").count(), 1);
+        assert!(!svg.contains(r#"id="100""#));
+    }
+
+    #[test]
+    fn invert_leaves_out_the_listed_relations_members() {
+        let objs = square(1, 0.0, 0.0)
+            .into_iter()
+            .chain([
+                way(10, &[1, 2], &[]),
+                way(11, &[3, 4], &[]),
+                way(12, &[4, 1], &[]),
+                relation(100, &[(OsmId::Way(WayId(10)), "")], &[("type", "route")]),
+                relation(101, &[(OsmId::Way(WayId(11)), "")], &[("type", "route")]),
+            ])
+            .map(|o| (o, None))
+            .collect::<Vec<_>>();
+        let selection = select_from("invert", &objs, &["--invert"], &[], &[100]).unwrap();
+        assert_eq!(selection.relations, [RelationId(101)].into());
+        // Way 10 is only in the relation left out, and way 11 is drawn as part of 101.
+        assert_eq!(selection.ways, [WayId(12)].into());
+        let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf", "--invert"]);
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let objs = &selection.objs;
+        let mut renderer = Renderer::new(objs, objs, &changes, &edit_times, &args, None);
+        let svg = svg_string(
+            &args,
+            &renderer.render(&selection.relations, &selection.ways),
+        );
+        assert!(svg.contains(r#"id="101""#) && svg.contains(r#"id="11""#));
+        assert!(!svg.contains(r#"id="100""#) && !svg.contains(r#"id="10""#));
+        assert_eq!(svg.matches(r#"id="11""#).count(), 1);
+    }
+
+    #[test]
+    fn streaming_draws_what_drawing_in_memory_does() {
+        let objs = objects(
+            [square(1, 0.0, 0.0), square(11, 5.0, 5.0)]
+                .into_iter()
+                .flatten()
+                .chain([
+                    OsmObj::Node(node(21, -5.0, -5.0)),
+                    OsmObj::Node(node(22, -5.0, -4.9999)),
+                    OsmObj::Node(node(23, -4.9999, -5.0)),
+                    OsmObj::Node(node(24, 10.0, 10.0)),
+                    OsmObj::Node(node(25, 10.0, 10.0001)),
+                    way(10, &[1, 2, 3], &[]),
+                    way(11, &[11, 12, 13, 14, 11], &[]),
+                    way(12, &[21, 22, 23, 21], &[]),
+                    way(13, &[24, 25], &[]),
+                    relation(100, &[(OsmId::Way(WayId(10)), "")], &[]),
+                    relation(
+                        101,
+                        &[(OsmId::Way(WayId(11)), ""), (OsmId::Way(WayId(19)), "")],
+                        &[],
+                    ),
+                ]),
+        );
+        let flags = [
+            "--min-length",
+            "100",
+            "--collapse-small",
+            "10",
+            "--require-complete",
+        ];
+        let svg = draw(&objs, &flags, &[100, 101], &[12, 13]);
+        let args = Args::parse_from(
+            ["osm-rels-svg", "-d", "x.pbf", "--stream"]
+                .iter()
+                .chain(&flags),
+        );
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let mut renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, None);
+        let relations = [RelationId(100), RelationId(101)].into();
+        let mut streamed = Vec::new();
+        renderer
+            .stream(&relations, &[WayId(12), WayId(13)].into(), &mut streamed)
+            .unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), svg);
+        // Way 12 is collapsed into a dot, way 13 is too short, and relation 101 incomplete.
+        assert!(!svg.contains(r#"id="101""#));
+        assert!(svg.contains(r#"id="12""#) && !svg.contains(r#"id="13""#));
+    }
+
+    #[test]
+    fn reading_again_starts_the_progress_over() {
+        let progress = ProgressBar::hidden();
+        progress.set_length(8);
+        let mut reader = ReadProgress {
+            inner: std::io::Cursor::new([0; 8]),
+            progress: progress.clone(),
+        };
+        std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+        assert_eq!(progress.position(), 8);
+        reader.rewind().unwrap();
+        reader.read_exact(&mut [0; 3]).unwrap();
+        assert_eq!(progress.position(), 3);
+        reader.seek(std::io::SeekFrom::Start(6)).unwrap();
+        assert_eq!(progress.position(), 6);
+    }
+
+    #[test]
+    fn only_missing_objects_count_towards_max_warnings() {
+        let objs = objects(
+            square(1, 0.0, 0.0)
+                .into_iter()
+                .chain([way(10, &[1, 2, 9], &[])]),
+        );
+        let args = Args::parse_from([
+            "osm-rels-svg",
+            "-d",
+            "x.pbf",
+            "--max-warnings",
+            "0",
+            "--center-on-relation",
+            "100",
+            "--crop-to-relation",
+            "101",
+        ]);
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let mut renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, None);
+        renderer.render(&BTreeSet::new(), &[WayId(10), WayId(11)].into());
+        // Node 9 and way 11, but not the relations to crop to and center on.
+        assert_eq!(renderer.missing_warnings.get(), 2);
+    }
+
+    #[test]
+    fn relative_paths_add_up_to_the_absolute_ones() {
+        let nodes = (1..=50).map(|n| {
+            let t = n as f64 / 7.0;
+            OsmObj::Node(node(n, t.sin() * 0.3, t * 0.013))
+        });
+        let objs = objects(nodes.chain([way(10, &(1..=50).collect::<Vec<_>>(), &[])]));
+        let d = |svg: &str| {
+            let d = svg.split(" d=\"").nth(1).unwrap();
+            d[..d.find('"').unwrap()].to_string()
+        };
+        let points = |d: &str| {
+            d.split_whitespace()
+                .map(|command| {
+                    let (x, y) = command[1..].split_once(',').unwrap();
+                    (x.parse::<f64>().unwrap(), y.parse::<f64>().unwrap())
+                })
+                .collect::<Vec<_>>()
+        };
+        let absolute = points(&d(&draw(&objs, &[], &[], &[10])));
+        let relative = d(&draw(&objs, &["--relative-paths"], &[], &[10]));
+        assert!(relative.starts_with('m') && relative.contains(" l"));
+        let mut at = (0.0, 0.0);
+        let decoded = points(&relative)
+            .into_iter()
+            .map(|(dx, dy)| {
+                at = (at.0 + dx, at.1 + dy);
+                at
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(decoded.len(), absolute.len());
+        // Each point is the absolute one rounded to hundredths, however far along the way.
+        for (a, b) in absolute.iter().zip(&decoded) {
+            assert!((a.0 - b.0).abs() <= 0.005 + 1e-9 && (a.1 - b.1).abs() <= 0.005 + 1e-9);
+        }
+    }
+
+    #[test]
+    fn relations_to_center_on_are_loaded_without_being_drawn() {
+        let objs = [square(1, 0.0, 0.0), square(11, 2.0, 2.0)]
+            .into_iter()
+            .flatten()
+            .chain([
+                way(10, &[1, 2, 3, 4, 1], &[]),
+                way(11, &[11, 12, 13, 14, 11], &[]),
+                relation(100, &[(OsmId::Way(WayId(11)), "")], &[]),
+            ])
+            .map(|o| (o, None))
+            .collect::<Vec<_>>();
+        let flags = ["--center-on-relation", "100"];
+        let selection = select_from("center", &objs, &flags, &[10], &[]).unwrap();
+        assert!(selection
+            .objs
+            .contains_key(&OsmId::Relation(RelationId(100))));
+        assert_eq!(selection.relations, BTreeSet::new());
+        let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf"].iter().chain(&flags));
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let objs = &selection.objs;
+        let mut renderer = Renderer::new(objs, objs, &changes, &edit_times, &args, None);
+        let svg = svg_string(
+            &args,
+            &renderer.render(&selection.relations, &selection.ways),
+        );
+        // The view box keeps the size of square 10, but is centered on the relation's square.
+        let view_box = svg.split("viewBox=\"").nth(1).unwrap();
+        let view_box = view_box[..view_box.find('"').unwrap()]
+            .split(' ')
+            .map(|v| v.parse::<f64>().unwrap())
+            .collect::<Vec<_>>();
+        let (cx, cy) = renderer.center(&bound(2.0..3.0, 2.0..3.0));
+        assert!((view_box[0] + view_box[2] / 2.0 - cx).abs() < 1e-6);
+        assert!((view_box[1] + view_box[3] / 2.0 - cy).abs() < 1e-6);
+        assert!(!svg.contains(r#"id="11""#));
+    }
+
+    #[test]
+    fn elevations_are_colored_from_low_to_high() {
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            way(10, &[1, 2], &[("ele", "100")]),
+            way(11, &[2, 3], &[("ele", "150")]),
+            way(12, &[3, 4], &[("ele", "200")]),
+        ]));
+        let flags = [
+            "--ele-color",
+            "--ele-low",
+            "#000000",
+            "--ele-high",
+            "#ffffff",
+        ];
+        let svg = draw(&objs, &flags, &[], &[10, 11, 12]);
+        let stroke = |id| attribute(element(&svg, id), "stroke").unwrap();
+        assert_eq!(stroke("10"), "#000000");
+        assert_eq!(stroke("11"), "#808080");
+        assert_eq!(stroke("12"), "#ffffff");
+    }
+
+    #[test]
+    fn min_length_drops_only_the_short_ways() {
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            OsmObj::Node(node(5, 0.0, 0.0001)),
+            way(10, &[1, 2], &[]),
+            way(11, &[1, 5], &[]),
+        ]));
+        // A degree is about 11000 units, and node 5 is about 1 unit from node 1.
+        let svg = draw(&objs, &["--min-length", "100"], &[], &[10, 11]);
+        assert!(svg.contains(r#"id="10""#));
+        assert!(!svg.contains(r#"id="11""#));
+        let svg = draw(&objs, &["--min-length", "0.5"], &[], &[10, 11]);
+        assert!(svg.contains(r#"id="11""#));
+    }
+
+    #[test]
+    fn user_and_changeset_select_what_they_last_edited() {
+        let edit = |user, changeset| {
+            Some(Edit {
+                user,
+                changeset,
+                timestamp: 1_700_000_000,
+            })
+        };
+        let objs = square(1, 0.0, 0.0)
+            .into_iter()
+            .map(|o| (o, None))
+            .chain([
+                (way(10, &[1, 2], &[("highway", "path")]), edit("alice", 1)),
+                (way(11, &[2, 3], &[("highway", "path")]), edit("bob", 2)),
+                (way(12, &[3, 4], &[]), edit("alice", 3)),
+                (relation(100, &[], &[]), edit("bob", 2)),
+            ])
+            .collect::<Vec<_>>();
+        let selection = select_from("user", &objs, &["--user", "alice"], &[], &[]).unwrap();
+        assert_eq!(selection.ways, [WayId(10), WayId(12)].into());
+        assert_eq!(selection.relations, BTreeSet::new());
+        let flags = ["--user", "bob", "--changeset", "2"];
+        let selection = select_from("changeset", &objs, &flags, &[], &[]).unwrap();
+        assert_eq!(selection.ways, [WayId(11)].into());
+        assert_eq!(selection.relations, [RelationId(100)].into());
+        // With a tag filter, the user narrows down what it selects.
+        let flags = ["--user", "alice", "--way-tag", "highway=path"];
+        let selection = select_from("user-tag", &objs, &flags, &[], &[]).unwrap();
+        assert_eq!(selection.ways, [WayId(10)].into());
+        let selection = select_from("nobody", &objs, &["--user", "carol"], &[], &[]);
+        assert!(matches!(selection, Err(RenderError::EmptySelection)));
+    }
+
+    #[test]
+    fn deterministic_output_does_not_depend_on_member_order() {
+        let members = |order: &[i64]| {
+            order
+                .iter()
+                .map(|&w| (OsmId::Way(WayId(w)), ""))
+                .collect::<Vec<_>>()
+        };
+        let with_members = |order: &[i64], kind: &str| {
+            objects(square(1, 0.0, 0.0).into_iter().chain([
+                way(10, &[1, 2], &[]),
+                way(11, &[2, 3], &[]),
+                way(12, &[3, 4], &[]),
+                relation(100, &members(order), &[("type", kind)]),
+            ]))
+        };
+        let draw = |order: &[i64], kind: &str| {
+            draw(
+                &with_members(order, kind),
+                &["--deterministic"],
+                &[100],
+                &[],
+            )
+        };
+        let svg = draw(&[12, 10, 11], "multipolygon");
+        assert_eq!(svg, draw(&[11, 12, 10], "multipolygon"));
+        assert_eq!(svg, draw(&[12, 10, 11], "multipolygon"));
+        assert!(svg.find(r#"id="10""#) < svg.find(r#"id="12""#));
+        // Routes keep their order, which is the order they're travelled in.
+        let route = draw(&[12, 10, 11], "route");
+        assert!(route.find(r#"id="12""#) < route.find(r#"id="10""#));
+    }
+
+    #[test]
+    fn highlight_emphasizes_only_the_ids_given() {
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            way(11, &[2, 3], &[]),
+            way(12, &[3, 4], &[]),
+            relation(100, &[(OsmId::Way(WayId(12)), "")], &[]),
+        ]));
+        let flags = ["--highlight", "11,100", "--stroke-width", "1"];
+        let svg = draw(&objs, &flags, &[100], &[10, 11]);
+        let style = |id| {
+            let element = element(&svg, id);
+            (
+                attribute(element, "stroke"),
+                attribute(element, "stroke-width"),
+            )
+        };
+        let highlighted = (Some("#ff00ff"), Some("3"));
+        assert_eq!(style("11"), highlighted);
+        assert_eq!(style("100"), highlighted);
+        assert_eq!(style("10"), (None, None));
+        // Members of a highlighted relation are highlighted with it.
+        assert_eq!(style("12"), highlighted);
+    }
+
+    #[test]
+    fn higher_zoom_levels_keep_more_points() {
+        let nodes = (1..=40).map(|n| {
+            let t = n as f64 / 4.0;
+            OsmObj::Node(node(n, t.sin() * 0.01 * t, t * 0.01))
+        });
+        let objs = objects(nodes.chain([way(10, &(1..=40).collect::<Vec<_>>(), &[])]));
+        let flags = [
+            "osm-rels-svg",
+            "-d",
+            "x.pbf",
+            "-o",
+            "map.svg",
+            "--lod",
+            "8=50,16=0.5",
+        ];
+        let args = Args::parse_from(flags);
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let points = args
+            .lod
+            .iter()
+            .map(|&(_, tolerance)| {
+                let mut renderer =
+                    Renderer::new(&objs, &objs, &changes, &edit_times, &args, Some(tolerance));
+                let svg = svg_string(
+                    &args,
+                    &renderer.render(&BTreeSet::new(), &[WayId(10)].into()),
+                );
+                attribute(element(&svg, "10"), "d")
+                    .unwrap()
+                    .matches('L')
+                    .count()
+                    + 1
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(args.lod.iter().map(|l| l.0).collect::<Vec<_>>(), [8, 16]);
+        assert!(points[0] >= 2 && points[0] < points[1], "{points:?}");
+        assert!(parse_lod("14").is_err() && parse_lod("x=1").is_err());
+    }
+
+    /// The view box of a drawing, as `(x, y, width, height)`.
+    fn view_box(svg: &str) -> Vec<f64> {
+        attribute(&svg[..svg.find('>').unwrap()], "viewBox")
+            .unwrap()
+            .split(' ')
+            .map(|v| v.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn a_single_point_still_has_room_around_it() {
+        let objs = objects([
+            OsmObj::Node(node(1, 10.0, 20.0)),
+            OsmObj::Node(node(2, 10.0, 20.0)),
+            way(10, &[1, 2, 1], &[]),
+        ]);
+        let svg = draw(&objs, &[], &[], &[10]);
+        let view_box = view_box(&svg);
+        assert!(view_box.iter().all(|v| v.is_finite()));
+        assert!(view_box[2] > 0.0 && view_box[3] > 0.0);
+        assert_eq!(
+            (view_box[2], view_box[3]),
+            (2.0 * POINT_MARGIN, 2.0 * POINT_MARGIN)
+        );
+    }
+
+    #[test]
+    fn features_are_drawn_in_z_order() {
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            way(10, &[1, 2], &[("z_order", "5")]),
+            way(11, &[2, 3], &[("z-index", "-2")]),
+            way(12, &[3, 4], &[]),
+            way(13, &[4, 1], &[("z_order", "not a number")]),
+            relation(100, &[(OsmId::Way(WayId(12)), "")], &[("z_order", "3")]),
+        ]));
+        let svg = draw(&objs, &[], &[100], &[10, 11, 13]);
+        let at = |id: &str| svg.find(&format!(r#"id="{id}""#)).unwrap();
+        // Way 13's z-order can't be read, so it goes with the ways without one.
+        assert!(at("11") < at("100") && at("100") < at("10"));
+        assert!(at("11") < at("13") && at("13") < at("100"));
+    }
+
+    #[test]
+    fn json_log_lines_carry_the_kind_id_and_message() {
+        let message = r#"way 7 "quoted" not found"#.to_string();
+        let line = log_line(LogFormat::Json, "missing_way", 7, message.clone());
+        assert!(!line.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            value,
+            json!({ "kind": "missing_way", "id": 7, "message": message })
+        );
+        assert_eq!(
+            log_line(LogFormat::Text, "missing_way", 7, message.clone()),
+            message
+        );
+    }
+
+    #[test]
+    fn rotating_turns_the_drawing_about_its_center() {
+        let objs = objects([
+            OsmObj::Node(node(1, 0.0, 0.0)),
+            OsmObj::Node(node(2, 1.0, 2.0)),
+            way(10, &[1, 2], &[]),
+        ]);
+        let plain = view_box(&draw(&objs, &[], &[], &[10]));
+        let (cx, cy) = (plain[0] + plain[2] / 2.0, plain[1] + plain[3] / 2.0);
+        let svg = draw(&objs, &["--rotate", "90"], &[], &[10]);
+        let group = &svg[svg.find("<g transform=").unwrap()..];
+        let transform = attribute(&group[..group.find('>').unwrap()], "transform").unwrap();
+        assert_eq!(transform, format!("rotate(90 {cx} {cy})"));
+        let rotated = view_box(&svg);
+        assert!((rotated[2] - plain[3]).abs() < 1e-6 && (rotated[3] - plain[2]).abs() < 1e-6);
+        assert!((rotated[0] + rotated[2] / 2.0 - cx).abs() < 1e-6);
+        assert!((rotated[1] + rotated[3] / 2.0 - cy).abs() < 1e-6);
+        // Turned half as far the other way, the drawing's corners need room on every side.
+        let rotated = view_box(&draw(&objs, &["--rotate", "-45"], &[], &[10]));
+        let side = (plain[2] + plain[3]) * std::f64::consts::FRAC_1_SQRT_2;
+        assert!((rotated[2] - side).abs() < 1e-6 && (rotated[3] - side).abs() < 1e-6);
+    }
+
+    #[test]
+    fn topojson_shares_one_arc_between_relations() {
+        let shared = (OsmId::Way(WayId(10)), "");
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            way(10, &[1, 2], &[("highway", "path")]),
+            relation(100, &[shared], &[("type", "route")]),
+            relation(101, &[shared], &[("type", "route")]),
+        ]));
+        let relations = [RelationId(100), RelationId(101)].into();
+        let topology = topojson(&objs, &objs, &relations, &BTreeSet::new());
+        assert_eq!(topology["arcs"], json!([[[0.0, 0.0], [1.0, 0.0]]]));
+        let geometries = &topology["objects"]["collection"]["geometries"];
+        assert_eq!(geometries.as_array().unwrap().len(), 2);
+        for (geometry, id) in geometries.as_array().unwrap().iter().zip(["100", "101"]) {
+            assert_eq!(geometry["id"], format!("relation/{id}"));
+            let member = &geometry["geometries"][0];
+            assert_eq!(member["id"], "way/10");
+            assert_eq!(member["arcs"], json!([0]));
+            assert_eq!(member["properties"]["highway"], "path");
+        }
+    }
+
+    #[test]
+    fn ways_take_the_color_of_their_lowest_relation() {
+        let member = |w| (OsmId::Way(WayId(w)), "");
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            way(11, &[2, 3], &[]),
+            way(12, &[3, 4], &[]),
+            way(13, &[4, 1], &[]),
+            relation(100, &[member(10)], &[("colour", "#ff0000")]),
+            relation(101, &[member(11), member(10)], &[("colour", "#00ff00")]),
+            relation(102, &[member(13)], &[("colour", "red")]),
+        ]));
+        let ways = [10, 11, 12, 13];
+        let svg = draw(&objs, &["--color-by-relation"], &[], &ways);
+        let stroke = |id| attribute(element(&svg, id), "stroke");
+        assert_eq!(stroke("10"), Some("#ff0000"));
+        assert_eq!(stroke("11"), Some("#00ff00"));
+        assert_eq!(stroke("12"), None);
+        // Only colors given as hex are used.
+        assert_eq!(stroke("13"), None);
+        let svg = draw(&objs, &[], &[], &ways);
+        assert_eq!(attribute(element(&svg, "10"), "stroke"), None);
+    }
+
+    #[test]
+    fn stroke_width_sets_the_default_for_the_drawing() {
+        let objs = objects(
+            square(1, 0.0, 0.0)
+                .into_iter()
+                .chain([way(10, &[1, 2], &[])]),
+        );
+        let root = |svg: &str| svg[..svg.find('>').unwrap()].to_string();
+        let svg = draw(&objs, &[], &[], &[10]);
+        assert_eq!(
+            attribute(&root(&svg), "stroke-width"),
+            Some(STROKE_WIDTH.to_string().as_str())
+        );
+        let svg = draw(&objs, &["--stroke-width", "1.5"], &[], &[10]);
+        assert_eq!(attribute(&root(&svg), "stroke-width"), Some("1.5"));
+        assert!(!svg.contains("non-scaling-stroke"));
+        let svg = draw(
+            &objs,
+            &["--stroke-width", "2", "--stroke-units", "px"],
+            &[],
+            &[10],
+        );
+        assert_eq!(attribute(&root(&svg), "stroke-width"), Some("2"));
+        assert!(svg.contains("vector-effect: non-scaling-stroke"));
+        let negative = ["osm-rels-svg", "-d", "x.pbf", "--stroke-width", "-1"];
+        assert!(Args::try_parse_from(negative).is_err());
+    }
+
+    #[test]
+    fn layers_group_features_by_tag_value_and_toggle_in_html() {
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            way(10, &[1, 2], &[("highway", "path")]),
+            way(11, &[2, 3], &[("highway", "track")]),
+            way(12, &[3, 4], &[("highway", "path")]),
+            way(13, &[4, 1], &[]),
+        ]));
+        let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf", "--layers-by", "highway"]);
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let mut renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, None);
+        let ways = [10, 11, 12, 13].map(WayId).into();
+        let svg = svg_string(&args, &renderer.render(&BTreeSet::new(), &ways));
+        let layers = [
+            ("path".to_string(), "layer-path".to_string()),
+            ("track".to_string(), "layer-track".to_string()),
+        ];
+        assert_eq!(renderer.layers, layers);
+        // The untagged way goes below the layers, and each layer holds its value's ways.
+        let path = svg.find(r#"id="layer-path""#).unwrap();
+        let track = svg.find(r#"id="layer-track""#).unwrap();
+        assert!(svg.find(r#"id="13""#).unwrap() < path);
+        assert!(path < svg.find(r#"id="10""#).unwrap());
+        assert!(svg.find(r#"id="12""#).unwrap() < track);
+        assert!(track < svg.find(r#"id="11""#).unwrap());
+        let page = html(svg, &renderer.layers);
+        assert_eq!(page.matches("type=\"checkbox\"").count(), 2);
+        assert!(page.contains("getElementById('layer-track')"));
+        // Without layers, the page is just the drawing.
+        assert!(!html(String::new(), &[]).contains("fieldset"));
+        let conflicting = [
+            "osm-rels-svg",
+            "-d",
+            "x.pbf",
+            "--layers-by",
+            "a",
+            "--stream",
+        ];
+        assert!(Args::try_parse_from(conflicting).is_err());
+    }
+
+    #[test]
+    fn max_features_refuses_only_larger_selections() {
+        assert_eq!(check_feature_count(None, 1_000_000), Ok(()));
+        assert_eq!(check_feature_count(Some(3), 3), Ok(()));
+        assert_eq!(check_feature_count(Some(3), 0), Ok(()));
+        let error = check_feature_count(Some(3), 4).unwrap_err();
+        assert!(error.starts_with("4 features selected, more than --max-features 3"));
+    }
+
+    #[test]
+    fn group_attrs_describe_relations_by_their_tags() {
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            way(10, &[1, 2], &[("type", "ignored")]),
+            relation(
+                100,
+                &[(OsmId::Way(WayId(10)), "")],
+                &[("type", "route"), ("network", "NJT"), ("name:en", "A & B")],
+            ),
+        ]));
+        let flags = ["--group-attrs", "type,network,name:en,operator"];
+        let svg = draw(&objs, &flags, &[100], &[]);
+        let group = element(&svg, "100");
+        assert_eq!(attribute(group, "data-type"), Some("route"));
+        assert_eq!(attribute(group, "data-network"), Some("NJT"));
+        assert_eq!(attribute(group, "data-name-en"), Some("A &amp; B"));
+        assert_eq!(attribute(group, "data-operator"), None);
+        // Only relation groups get them, not the ways in them.
+        assert_eq!(attribute(element(&svg, "10"), "data-type"), None);
+        assert_eq!(
+            attribute(element(&draw(&objs, &[], &[100], &[]), "100"), "data-type"),
+            None
+        );
+    }
+
+    #[test]
+    fn casing_draws_a_wider_darker_path_first() {
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            way(10, &[1, 2], &[("colour", "#ff0000")]),
+            way(11, &[2, 3], &[]),
+        ]));
+        let svg = draw(&objs, &["--casing", "--stroke-width", "1"], &[], &[10, 11]);
+        let paths = svg
+            .split("<path")
+            .skip(1)
+            .map(|p| &p[..p.find('>').unwrap()])
+            .collect::<Vec<_>>();
+        assert_eq!(paths.len(), 4);
+        let (casing, path) = (paths[0], paths[1]);
+        assert_eq!(attribute(casing, "id"), None);
+        assert_eq!(attribute(path, "id"), Some("10"));
+        assert_eq!(attribute(casing, "d"), attribute(path, "d"));
+        assert_eq!(attribute(casing, "stroke-width"), Some("2"));
+        assert_eq!(attribute(casing, "stroke"), Some("#800000"));
+        // Ways without a stroke of their own are cased in the darkened default.
+        assert_eq!(attribute(paths[2], "stroke"), Some("#000000"));
+        assert_eq!(draw(&objs, &[], &[], &[10, 11]).matches("<path").count(), 2);
+    }
+
+    #[test]
+    fn unpaved_surfaces_are_dashed_or_dotted() {
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            way(10, &[1, 2], &[("surface", "gravel")]),
+            way(11, &[2, 3], &[("surface", "paved")]),
+            way(12, &[3, 4], &[("surface", "dirt")]),
+            way(13, &[4, 1], &[]),
+        ]));
+        let svg = draw(&objs, &["--stroke-width", "1"], &[], &[10, 11, 12, 13]);
+        let dashes = |id| attribute(element(&svg, id), "stroke-dasharray");
+        assert_eq!(dashes("10"), Some("3 2"));
+        assert_eq!(dashes("11"), None);
+        assert_eq!(dashes("12"), Some("0 2"));
+        assert_eq!(dashes("13"), None);
+    }
+
+    #[test]
+    fn markers_share_one_symbol_definition() {
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([relation(
+            100,
+            &[
+                (OsmId::Node(NodeId(1)), "stop"),
+                (OsmId::Node(NodeId(2)), "stop"),
+            ],
+            &[],
+        )]));
+        let svg = draw(&objs, &["--node-markers"], &[100], &[]);
+        assert_eq!(svg.matches("<symbol").count(), 1);
+        assert_eq!(svg.matches(r##"href="#node-marker""##).count(), 2);
+        assert!(svg.find("<defs").unwrap() < svg.find("<use").unwrap());
+        // Without markers to draw, there's nothing to define.
+        let svg = draw(&objs, &[], &[100], &[]);
+        assert!(!svg.contains("<symbol") && !svg.contains("<use"));
+    }
+
+    #[test]
+    fn crop_to_relation_clips_to_its_area() {
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            OsmObj::Node(node(5, 0.5, 3.0)),
+            way(10, &[1, 2, 3, 4, 1], &[]),
+            way(11, &[1, 3, 5], &[]),
+            relation(
+                100,
+                &[(OsmId::Way(WayId(10)), "outer")],
+                &[("type", "multipolygon")],
+            ),
+        ]));
+        let svg = draw(&objs, &["--crop-to-relation", "100"], &[100], &[11]);
+        let clip = &svg[svg.find(r#"<clipPath id="crop">"#).unwrap()..];
+        let clip = &clip[..clip.find("</clipPath>").unwrap()];
+        assert_eq!(clip.matches("<path").count(), 1);
+        assert!(clip.contains(r#"clip-rule="evenodd""#));
+        // The way out past the square is clipped, and the relation drawn above, unclipped.
+        let clipped = svg.find(r##"clip-path="url(#crop)""##).unwrap();
+        assert!(clipped < svg.find(r#"id="11""#).unwrap());
+        let end = svg[clipped..].find("</g>").unwrap() + clipped;
+        assert!(svg.find(r#"id="11""#).unwrap() < end);
+        assert!(svg.find(r#"<g id="100""#).unwrap() > end);
+        let svg = draw(&objs, &["--crop-to-relation", "101"], &[], &[11]);
+        assert!(!svg.contains("clip-path") && svg.contains(r#"id="11""#));
+    }
+
+    #[test]
+    fn endpoints_mark_each_end_once() {
+        let objs = objects(
+            square(1, 0.0, 0.0)
+                .into_iter()
+                .chain([way(10, &[1, 2, 3], &[]), way(11, &[3, 4], &[])]),
+        );
+        let endpoints = |svg: &str| {
+            let group = &svg[svg.find(r#"<g id="endpoints">"#).unwrap()..];
+            group[..group.find("</g>").unwrap()].to_string()
+        };
+        let group = endpoints(&draw(&objs, &["--endpoints"], &[], &[10]));
+        assert_eq!(group.matches("<use").count(), 2);
+        assert_eq!(group.matches(r##"href="#node-marker""##).count(), 2);
+        // Where the two ways meet is marked once, as a junction.
+        let group = endpoints(&draw(&objs, &["--endpoints"], &[], &[10, 11]));
+        assert_eq!(group.matches("<use").count(), 3);
+        assert_eq!(group.matches(r##"href="#junction-marker""##).count(), 1);
+        assert!(!draw(&objs, &[], &[], &[10]).contains("endpoints"));
+    }
+
+    #[test]
+    fn filled_ways_carry_the_fill_rule() {
+        // A bowtie, crossing itself between nodes 2 and 3.
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            way(10, &[1, 3, 2, 4, 1], &[("building", "yes")]),
+            way(11, &[1, 3, 2, 4, 1], &[]),
+        ]));
+        let rule = |flags: &[&str], id| {
+            let svg = draw(&objs, flags, &[], &[10, 11]);
+            attribute(element(&svg, id), "fill-rule").map(str::to_string)
+        };
+        assert_eq!(rule(&["--fill"], "10").as_deref(), Some("nonzero"));
+        let evenodd = ["--fill", "--fill-rule", "evenodd"];
+        assert_eq!(rule(&evenodd, "10").as_deref(), Some("evenodd"));
+        // Only what's filled needs a rule.
+        assert_eq!(rule(&evenodd, "11"), None);
+        assert_eq!(rule(&["--fill-rule", "evenodd"], "10"), None);
+    }
+
+    #[test]
+    fn tags_out_has_a_row_per_drawn_feature() {
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            way(
+                10,
+                &[1, 2],
+                &[("name", "Main St, North"), ("highway", "primary")],
+            ),
+            way(11, &[2, 3], &[("highway", "path")]),
+            relation(100, &[(OsmId::Way(WayId(11)), "")], &[("type", "route")]),
+        ]));
+        let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf"]);
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let mut renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, None);
+        renderer.render(&[RelationId(100)].into(), &[WayId(10), WayId(12)].into());
+        let path = temp_path("tags.csv");
+        save_tags(&path, &objs, &renderer.rendered).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            [
+                "osm_type,osm_id,highway,name,type",
+                "way,10,primary,\"Main St, North\",",
+                "way,11,path,,",
+                "relation,100,,,route",
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_nodes_are_drawn_once() {
+        let objs = objects(square(1, 0.0, 0.0).into_iter().chain([
+            OsmObj::Node(node(5, 0.0, 1.0)),
+            way(10, &[1, 2, 2, 3], &[]),
+            way(11, &[1, 2, 5, 3], &[]),
+            way(12, &[1, 2, 3], &[]),
+        ]));
+        let svg = draw(&objs, &[], &[], &[10, 11, 12]);
+        let d = |id| attribute(element(&svg, id), "d").unwrap();
+        // Node 5 is a different node in the same place as node 2.
+        assert_eq!(d("10"), d("12"));
+        assert_eq!(d("11"), d("12"));
+        assert_eq!(d("12").matches('L').count(), 2);
+    }
+
+    #[test]
+    fn relation_labels_go_in_the_middle_of_the_relation() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2, 3], &[]),
+            relation(
+                100,
+                &[(OsmId::Way(WayId(10)), "")],
+                &[("name", "Ring & Road")],
+            ),
+            relation(101, &[(OsmId::Way(WayId(10)), "")], &[]),
+        ]));
+        let svg = draw(&objs, &["--relation-labels"], &[100, 101], &[]);
+        assert_eq!(svg.matches("<text").count(), 1);
+        let text = &svg[svg.find("<text").unwrap()..];
+        let content = &text[text.find('>').unwrap() + 1..text.find("</text>").unwrap()];
+        assert_eq!(content.trim(), "Ring &amp; Road");
+        let text = &text[..text.find('>').unwrap()];
+        let (x, y) = (attribute(text, "x").unwrap(), attribute(text, "y").unwrap());
+        let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf"]);
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, None);
+        let (cx, cy) = renderer.center(&bound(10.0..11.0, 10.0..11.0));
+        assert_eq!((x, y), (cx.to_string().as_str(), cy.to_string().as_str()));
+        assert_eq!(attribute(text, "text-anchor"), Some("middle"));
+        assert!(!draw(&objs, &[], &[100], &[]).contains("<text"));
+    }
+
+    #[test]
+    fn png_size_follows_the_dpi() {
+        let svg = Document::new()
+            .set("viewBox", (0, 0, 10, 20))
+            .add(Path::new().set("d", "M0,0 L10,20").set("stroke", "#000000"));
+        // The width and height are the first fields of the header chunk.
+        let size = |png: Vec<u8>| {
+            let field = |at: usize| u32::from_be_bytes(png[at..at + 4].try_into().unwrap());
+            (field(16), field(20))
+        };
+        assert_eq!(size(png(&svg, 96.0).unwrap()), (10, 20));
+        assert_eq!(size(png(&svg, 192.0).unwrap()), (20, 40));
+        let zero = ["osm-rels-svg", "-d", "x.pbf", "--dpi", "0"];
+        assert!(Args::try_parse_from(zero).is_err());
+    }
+
+    #[test]
+    fn no_viewbox_leaves_only_the_absolute_coordinates() {
+        let objs = objects(
+            square(1, 10.0, 10.0)
+                .into_iter()
+                .chain([way(10, &[1, 2, 3], &[])]),
+        );
+        let with = draw(&objs, &[], &[], &[10]);
+        let without = draw(&objs, &["--no-viewbox"], &[], &[10]);
+        assert!(with.contains("viewBox="));
+        assert!(!without.contains("viewBox="));
+        let d = |svg: &str| attribute(element(svg, "10"), "d").unwrap().to_string();
+        assert_eq!(d(&with), d(&without));
+        let conflicting = [
+            "osm-rels-svg",
+            "-d",
+            "x.pbf",
+            "--no-viewbox",
+            "--world-file",
+            "w",
+        ];
+        assert!(Args::try_parse_from(conflicting).is_err());
+    }
+
+    #[test]
+    fn legend_labels_the_ends_of_the_elevation_ramp() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[("ele", "120.5")]),
+            way(11, &[2, 3], &[("ele", "900")]),
+        ]));
+        let flags = ["--ele-color", "--legend", "--ele-low", "#000000"];
+        let svg = draw(&objs, &flags, &[], &[10, 11]);
+        println!("{svg}");
+        let ramp = &svg[svg.find(r#"<linearGradient id="legend-ramp">"#).unwrap()..];
+        let ramp = &ramp[..ramp.find("</linearGradient>").unwrap()];
+        assert!(
+            ramp.contains(r##"stop-color="#000000""##)
+                && ramp.contains(r##"stop-color="#ffffff""##)
+        );
+        let legend = &svg[svg.find(r#"<g id="legend""#).unwrap()..];
+        assert!(legend.contains(r##"fill="url(#legend-ramp)""##));
+        let labels = legend
+            .split("<text")
+            .skip(1)
+            .map(|t| t[t.find('>').unwrap() + 1..t.find("</text>").unwrap()].trim())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["120.5", "900"]);
+        // Without elevations, or without --ele-color, there's no ramp to show.
+        assert!(!draw(&objs, &["--legend"], &[], &[10, 11]).contains("legend"));
+    }
+
+    #[test]
+    fn reproject_only_writes_a_row_per_node() {
+        let objs = objects(
+            square(1, 10.0, 10.0)
+                .into_iter()
+                .chain([way(10, &[2, 1], &[])]),
+        );
+        let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf", "--reproject-only"]);
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let mut renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, None);
+        renderer.render(&BTreeSet::new(), &[WayId(10)].into());
+        let path = temp_path("projected.csv");
+        save_projected(Some(&path), &renderer.projected).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let row = |n: i64, lat, lon| {
+            let (x, y) = renderer.project(lat, lon);
+            format!("{n},{x},{y}")
+        };
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            [
+                "osm_node_id,x,y".to_string(),
+                row(1, 10.0, 10.0),
+                row(2, 10.0, 11.0)
+            ]
+        );
+        let conflicting = [
+            "osm-rels-svg",
+            "-d",
+            "x.pbf",
+            "--reproject-only",
+            "--stream",
+        ];
+        assert!(Args::try_parse_from(conflicting).is_err());
+    }
+
+    #[test]
+    fn missing_member_relations_are_counted_once_each() {
+        let child = |r| (OsmId::Relation(RelationId(r)), "");
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            relation(
+                100,
+                &[child(101), child(102), (OsmId::Way(WayId(11)), "")],
+                &[],
+            ),
+            relation(101, &[(OsmId::Way(WayId(10)), "")], &[]),
+        ]));
+        let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf"]);
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let mut renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, None);
+        renderer.render(&[RelationId(100)].into(), &BTreeSet::new());
+        // Relation 102 is missing, and so is way 11, which isn't a relation.
+        assert_eq!(renderer.stats.missing_relations, 1);
+        assert_eq!(renderer.missing_warnings.get(), 2);
+        assert_eq!(renderer.stats.empty_relations, 0);
+    }
+
+    #[test]
+    fn width_tags_set_the_stroke_width_within_the_clamps() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[("width", "10000")]),
+            way(11, &[2, 3], &[("width", "3 m")]),
+            way(12, &[3, 4], &[("width", "0.1")]),
+            way(13, &[4, 1], &[("width", "wide")]),
+        ]));
+        let flags = ["--width-tag", "--width-min", "1"];
+        let svg = draw(&objs, &flags, &[], &[10, 11, 12, 13]);
+        let width = |id| attribute(element(&svg, id), "stroke-width").map(str::to_string);
+        // Metres are converted at the latitude of each way's first node.
+        let projected = |meters, lat| Some(meters_to_projected(meters, lat).to_string());
+        assert_eq!(width("10"), projected(100.0, 10.0));
+        assert_eq!(width("11"), projected(3.0, 10.0));
+        assert_eq!(width("12"), projected(1.0, 11.0));
+        assert_eq!(width("13"), None);
+        assert_eq!(
+            attribute(element(&draw(&objs, &[], &[], &[10]), "10"), "stroke-width"),
+            None
+        );
+    }
+
+    #[test]
+    fn swapping_reflects_nodes_across_the_diagonal() {
+        let objs = [
+            OsmObj::Node(node(1, 10.0, 20.0)),
+            OsmObj::Node(node(2, 30.0, 40.0)),
+            way(10, &[1, 2], &[]),
+        ]
+        .into_iter()
+        .map(|o| (o, None))
+        .collect::<Vec<_>>();
+        let position = |flags: &[&str]| {
+            let selection = select_from("swap", &objs, flags, &[10], &[]).unwrap();
+            selection.objs[&OsmId::Node(NodeId(1))]
+                .node()
+                .map(|n| (n.lat(), n.lon()))
+        };
+        assert_eq!(position(&[]), Some((10.0, 20.0)));
+        assert_eq!(position(&["--swap-latlon"]), Some((20.0, 10.0)));
+    }
+
+    #[test]
+    fn directional_members_get_their_own_shades() {
+        let members = [
+            (OsmId::Way(WayId(10)), "forward"),
+            (OsmId::Way(WayId(11)), "backward"),
+            (OsmId::Way(WayId(12)), ""),
+        ];
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            way(11, &[2, 3], &[]),
+            way(12, &[3, 4], &[]),
+            relation(100, &members, &[("type", "route"), ("colour", "#ff0000")]),
+        ]));
+        let svg = draw(&objs, &["--color-by-role"], &[100], &[]);
+        let stroke = |id| attribute(element(&svg, id), "stroke");
+        assert_eq!(stroke("100"), Some("#ff0000"));
+        let (forward, backward) = (stroke("10").unwrap(), stroke("11").unwrap());
+        assert!(forward != backward && forward != "#ff0000" && backward != "#ff0000");
+        // Members without a direction take the route's color from the group.
+        assert_eq!(stroke("12"), None);
+        let svg = draw(&objs, &[], &[100], &[]);
+        assert_eq!(attribute(element(&svg, "10"), "stroke"), None);
+    }
+
+    #[test]
+    fn scale_bar_in_miles_measures_miles() {
+        let view_box = (0.0, 0.0, 40000.0, 1000.0);
+        let svg = scale_bar(view_box, 60.0, DistanceUnit::Mi, 1.0).to_string();
+        assert_eq!(svg.lines().nth(3), Some("20 mi"));
+        // The bar runs from its second point to its third.
+        let d = attribute(svg.lines().nth(1).unwrap(), "d").unwrap();
+        let x = |i: usize| {
+            let point = &d.split(' ').nth(i).unwrap()[1..];
+            point.split(',').next().unwrap().parse::<f64>().unwrap()
+        };
+        let length = x(2) - x(1);
+        assert!((length - 20.0 * meters_to_projected(1609.344, 60.0)).abs() < 0.01);
+        let svg = scale_bar(view_box, 60.0, DistanceUnit::Km, 1.0).to_string();
+        assert_eq!(svg.lines().nth(3), Some("50 km"));
+    }
+
+    #[test]
+    fn require_complete_leaves_out_relations_missing_a_member() {
+        let members = [(OsmId::Way(WayId(10)), ""), (OsmId::Way(WayId(11)), "")];
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            way(12, &[3, 4], &[]),
+            relation(100, &members, &[("type", "route")]),
+            relation(101, &[(OsmId::Way(WayId(12)), "")], &[("type", "route")]),
+        ]));
+        let svg = draw(&objs, &[], &[100, 101], &[]);
+        assert!(svg.contains(r#"id="100""#) && svg.contains(r#"id="10""#));
+        let svg = draw(&objs, &["--require-complete"], &[100, 101], &[]);
+        assert!(!svg.contains(r#"id="100""#) && !svg.contains(r#"id="10""#));
+        assert!(svg.contains(r#"id="101""#) && svg.contains(r#"id="12""#));
+    }
+
+    #[test]
+    fn gradients_along_ways_run_from_their_start_to_their_end() {
+        let objs = objects(
+            square(1, 10.0, 10.0)
+                .into_iter()
+                .chain([way(10, &[1, 2, 3], &[])]),
+        );
+        let svg = draw(&objs, &["--gradient-along"], &[], &[10]);
+        assert_eq!(
+            attribute(element(&svg, "10"), "stroke"),
+            Some("url(#gradient-10)")
+        );
+        let gradient = element(&svg, "gradient-10");
+        let coordinate = |name| attribute(gradient, name).unwrap().parse::<f64>().unwrap();
+        let points = attribute(element(&svg, "10"), "d")
+            .unwrap()
+            .split(' ')
+            .map(|p| {
+                let (x, y) = p[1..].split_once(',').unwrap();
+                (x.parse::<f64>().unwrap(), y.parse::<f64>().unwrap())
+            })
+            .collect::<Vec<_>>();
+        let (start, end) = (points[0], points[2]);
+        assert!((coordinate("x1") - start.0).abs() < 0.01);
+        assert!((coordinate("y1") - start.1).abs() < 0.01);
+        assert!((coordinate("x2") - end.0).abs() < 0.01);
+        assert!((coordinate("y2") - end.1).abs() < 0.01);
+        let flags = [
+            "osm-rels-svg",
+            "-d",
+            "x.pbf",
+            "--gradient-along",
+            "--curvature-color",
+        ];
+        assert!(Args::try_parse_from(flags).is_err());
+    }
+
+    #[test]
+    fn only_types_leaves_out_other_kinds_of_member() {
+        let members = [
+            (OsmId::Way(WayId(10)), ""),
+            (OsmId::Node(NodeId(3)), "stop"),
+        ];
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            relation(100, &members, &[("type", "route")]),
+        ]));
+        let svg = draw(&objs, &["--node-markers"], &[100], &[]);
+        assert!(svg.contains(r#"id="10""#) && svg.contains(r##"href="#node-marker""##));
+        let svg = draw(
+            &objs,
+            &["--node-markers", "--only-types", "way"],
+            &[100],
+            &[],
+        );
+        assert!(svg.contains(r#"id="10""#) && !svg.contains("<use"));
+        let svg = draw(
+            &objs,
+            &["--node-markers", "--only-types", "node"],
+            &[100],
+            &[],
+        );
+        assert!(!svg.contains(r#"id="10""#) && svg.contains("<use"));
+    }
+
+    #[test]
+    fn the_color_seed_reshuffles_auto_colors_reproducibly() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            way(11, &[3, 4], &[]),
+            relation(100, &[(OsmId::Way(WayId(10)), "")], &[("type", "route")]),
+            relation(101, &[(OsmId::Way(WayId(11)), "")], &[("type", "route")]),
+        ]));
+        let colors = |seed: &str| {
+            let svg = draw(
+                &objs,
+                &["--auto-color", "--color-seed", seed],
+                &[100, 101],
+                &[],
+            );
+            ["100", "101"].map(|id| attribute(element(&svg, id), "stroke").unwrap().to_string())
+        };
+        let first = colors("1");
+        assert_ne!(first[0], first[1]);
+        assert_eq!(colors("1"), first);
+        let second = colors("2");
+        assert_ne!(second[0], first[0]);
+        assert_ne!(second[1], first[1]);
+    }
+
+    #[test]
+    fn pretty_output_is_indented_and_compact_output_is_not() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            relation(100, &[(OsmId::Way(WayId(10)), "")], &[("type", "route")]),
+        ]));
+        let pretty = draw(&objs, &["--pretty"], &[100], &[]);
+        assert!(pretty.contains("\n  <g") && pretty.contains("\n    <path"));
+        let compact = draw(&objs, &["--compact"], &[100], &[]);
+        assert!(!compact.contains('\n') && compact.contains("><g"));
+        // Both have the same elements, only the whitespace between them differs.
+        let stripped = pretty.lines().map(str::trim).collect::<String>();
+        assert_eq!(stripped, compact);
+        let flags = ["osm-rels-svg", "-d", "x.pbf", "--pretty", "--compact"];
+        assert!(Args::try_parse_from(flags).is_err());
+    }
+
+    #[test]
+    fn debug_colors_tell_ways_apart_by_id() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[("colour", "#00ff00")]),
+            way(11, &[3, 4], &[]),
+        ]));
+        let colors = || {
+            let svg = draw(&objs, &["--debug-colors"], &[], &[10, 11]);
+            ["10", "11"].map(|id| attribute(element(&svg, id), "stroke").unwrap().to_string())
+        };
+        let first = colors();
+        assert_eq!(
+            first,
+            [DEBUG_PALETTE[2], DEBUG_PALETTE[3]].map(|c| c.to_string())
+        );
+        assert_eq!(colors(), first);
+    }
+
+    #[test]
+    fn simplifying_keeps_the_minimum_number_of_points() {
+        // A gentle arc, all of it within a tolerance of a whole degree of the chord.
+        let arc = (0..6).map(|i| {
+            let (lon, lat) = (10.0 + 0.2 * i as f64, 10.0 + 0.01 * (i * (5 - i)) as f64);
+            OsmObj::Node(node(i + 1, lat, lon))
+        });
+        let objs = objects(arc.chain([way(10, &[1, 2, 3, 4, 5, 6], &[])]));
+        let points = |flags: &[&str]| {
+            let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf"].iter().chain(flags));
+            let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+            let tolerance = Some(11119.0);
+            let mut renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, tolerance);
+            let svg = svg_string(
+                &args,
+                &renderer.render(&BTreeSet::new(), &[WayId(10)].into()),
+            );
+            attribute(element(&svg, "10"), "d")
+                .unwrap()
+                .split(' ')
+                .count()
+        };
+        assert_eq!(points(&[]), 2);
+        assert!(points(&["--simplify-min-points", "4"]) >= 4);
+        // A way with fewer points than the minimum keeps all of them.
+        assert_eq!(points(&["--simplify-min-points", "10"]), 6);
+    }
+
+    #[test]
+    fn shadows_are_defined_once_and_cast_by_the_content() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            relation(100, &[(OsmId::Way(WayId(10)), "")], &[("type", "route")]),
+        ]));
+        let svg = draw(&objs, &["--shadow"], &[100], &[]);
+        assert!(element(&svg, "shadow").starts_with("<filter"));
+        assert!(svg.contains("<feDropShadow"));
+        // The shadow is cast by one group holding everything drawn.
+        assert_eq!(svg.matches("url(#shadow)").count(), 1);
+        let shadowed = svg.find(r#"filter="url(#shadow)""#).unwrap();
+        assert!(shadowed < svg.find(r#"id="100""#).unwrap());
+        let svg = draw(&objs, &[], &[100], &[]);
+        assert!(!svg.contains("shadow"));
+        let flags = ["osm-rels-svg", "-d", "x.pbf", "--shadow", "--stream"];
+        assert!(Args::try_parse_from(flags).is_err());
+    }
+
+    #[test]
+    fn osmc_symbols_color_routes() {
+        let hiking = |id, symbol| {
+            let tags = [
+                ("type", "route"),
+                ("osmc:symbol", symbol),
+                ("colour", "#00ff00"),
+            ];
+            relation(id, &[(OsmId::Way(WayId(10)), "")], &tags)
+        };
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            hiking(100, "red:white:red_bar"),
+            hiking(101, ":white:blue_bar"),
+            hiking(102, "plaid:white:plaid_bar"),
+        ]));
+        let svg = draw(&objs, &[], &[100, 101, 102], &[]);
+        let stroke = |id| attribute(element(&svg, id), "stroke");
+        assert_eq!(stroke("100"), Some("#ff0000"));
+        // Without a way color the foreground's is used, and without either the `colour` tag.
+        assert_eq!(stroke("101"), Some("#0000ff"));
+        assert_eq!(stroke("102"), Some("#00ff00"));
+    }
+
+    #[test]
+    fn bbox_from_relation_frames_the_relation() {
+        let objs = square(1, 10.0, 10.0)
+            .into_iter()
+            .chain([
+                OsmObj::Node(node(5, 20.0, 20.0)),
+                way(10, &[1, 2, 3], &[]),
+                way(11, &[3, 5], &[]),
+                relation(100, &[(OsmId::Way(WayId(10)), "")], &[("type", "route")]),
+            ])
+            .map(|o| (o, None))
+            .collect::<Vec<_>>();
+        let path = write_pbf("bbox-from-relation", &objs);
+        let data = path.to_str().unwrap();
+        let flags = ["osm-rels-svg", "-d", data, "--bbox-from-relation", "100"];
+        let mut args = Args::parse_from(flags);
+        let ways = [WayId(10), WayId(11)].into();
+        let selection = select(&mut args, ways, BTreeSet::new()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let objs_read = &selection.objs;
+        let mut renderer = Renderer::new(objs_read, objs_read, &changes, &edit_times, &args, None);
+        let svg = svg_string(
+            &args,
+            &renderer.render(&selection.relations, &selection.ways),
+        );
+        let relation_alone = draw(objs_read, &[], &[100], &[]);
+        assert_eq!(view_box(&svg), view_box(&relation_alone));
+        assert_ne!(
+            view_box(&svg),
+            view_box(&draw(objs_read, &[], &[100], &[11]))
+        );
+        // A relation that isn't in the extract has no extent to go by.
+        let flags = ["--bbox-from-relation", "200"];
+        let missing = select_from("bbox-from-missing", &objs, &flags, &[10], &[]);
+        let id = OsmId::Relation(RelationId(200));
+        assert!(matches!(missing, Err(RenderError::MissingReference { id: i }) if i == id));
+    }
+
+    #[test]
+    fn diffs_color_ways_by_how_they_changed() {
+        let old = square(1, 10.0, 10.0)
+            .into_iter()
+            .chain([
+                way(10, &[1, 2], &[]),
+                way(12, &[3, 4], &[]),
+                way(13, &[4, 1], &[("highway", "path")]),
+            ])
+            .map(|o| (o, None))
+            .collect::<Vec<_>>();
+        let path = write_pbf("diff-old", &old);
+        let mut objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            way(11, &[2, 3], &[]),
+            way(13, &[4, 1], &[("highway", "track")]),
+        ]));
+        // Way 12 is listed, but only the older extract has it.
+        let mut ways = [10, 11, 12, 13].map(WayId).into();
+        let changes = diff(&path, &mut objs, &mut ways, &BTreeSet::new()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(objs.contains_key(&OsmId::Way(WayId(12))));
+        assert!(!changes.contains_key(&WayId(10)));
+        let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf"]);
+        let edit_times = BTreeMap::new();
+        let mut renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, None);
+        let svg = svg_string(&args, &renderer.render(&BTreeSet::new(), &ways));
+        let stroke = |id| attribute(element(&svg, id), "stroke").map(str::to_string);
+        assert_eq!(stroke("10"), None);
+        assert_eq!(stroke("11"), Some(Change::Added.color().to_string()));
+        assert_eq!(stroke("12"), Some(Change::Removed.color().to_string()));
+        assert_eq!(stroke("13"), Some(Change::Modified.color().to_string()));
+    }
+
+    #[test]
+    fn close_areas_closes_unclosed_areas_only() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2, 3, 4], &[("area", "yes")]),
+            way(11, &[1, 2, 3, 4], &[("highway", "path")]),
+        ]));
+        let flags = ["--fill", "--close-areas"];
+        let svg = draw(&objs, &flags, &[], &[10, 11]);
+        // Closed as closed ways are, by going back to the first point.
+        let closed = |svg: &str, id| {
+            let points = attribute(element(svg, id), "d")
+                .unwrap()
+                .split(' ')
+                .collect::<Vec<_>>();
+            points[0][1..] == points[points.len() - 1][1..]
+        };
+        assert!(closed(&svg, "10"));
+        assert_eq!(attribute(element(&svg, "10"), "fill"), Some("#cccccc"));
+        assert!(!closed(&svg, "11"));
+        assert_eq!(attribute(element(&svg, "11"), "fill"), Some("none"));
+        let svg = draw(&objs, &["--fill"], &[], &[10]);
+        assert!(!closed(&svg, "10"));
+        assert_eq!(attribute(element(&svg, "10"), "fill"), Some("none"));
+    }
+
+    #[test]
+    fn offsets_shift_everything_drawn_and_the_view_box_alike() {
+        let objs = objects(
+            square(1, 10.0, 10.0)
+                .into_iter()
+                .chain([way(10, &[1, 2, 3], &[])]),
+        );
+        let plain = draw(&objs, &[], &[], &[10]);
+        let shifted = draw(&objs, &["--offset-x", "100", "--offset-y=-50"], &[], &[10]);
+        let points = |svg: &str| {
+            attribute(element(svg, "10"), "d")
+                .unwrap()
+                .split(' ')
+                .map(|p| {
+                    let (x, y) = p[1..].split_once(',').unwrap();
+                    (x.parse::<f64>().unwrap(), y.parse::<f64>().unwrap())
+                })
+                .collect::<Vec<_>>()
+        };
+        let close = |a: f64, b: f64| (a - b).abs() < 0.011;
+        for (p, q) in points(&plain).into_iter().zip(points(&shifted)) {
+            assert!(
+                close(q.0, p.0 + 100.0) && close(q.1, p.1 - 50.0),
+                "{p:?} {q:?}"
+            );
+        }
+        let (plain, shifted) = (view_box(&plain), view_box(&shifted));
+        assert!(close(shifted[0], plain[0] + 100.0) && close(shifted[1], plain[1] - 50.0));
+        assert_eq!(shifted[2..], plain[2..]);
+    }
+
+    #[test]
+    fn the_overview_is_a_miniature_in_the_corner() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            OsmObj::Node(node(5, 14.0, 14.0)),
+            way(10, &[1, 2, 3], &[]),
+            way(11, &[3, 5], &[]),
+        ]));
+        let svg = draw(
+            &objs,
+            &["--overview", "--bbox", "10,10,11,11"],
+            &[],
+            &[10, 11],
+        );
+        let main = view_box(&svg);
+        let overview = element(&svg, "overview");
+        let size = |name| attribute(overview, name).unwrap().parse::<f64>().unwrap();
+        assert!(size("width") <= main[2] / 4.0 + 1e-9 && size("height") <= main[3] / 4.0 + 1e-9);
+        let x = size("x");
+        assert!(main[0] <= x && x + size("width") <= main[0] + main[2]);
+        // The miniature shows everything, not just what the bbox lets through.
+        let full = attribute(overview, "viewBox").unwrap();
+        let full = full
+            .split(' ')
+            .map(|v| v.parse::<f64>().unwrap())
+            .collect::<Vec<_>>();
+        assert!(full[2] > main[2] && full[3] > main[3]);
+        assert!(svg.contains(r##"href="#overview-source""##));
+        assert!(!draw(&objs, &[], &[], &[10, 11]).contains("overview"));
+    }
+
+    #[test]
+    fn collapse_small_draws_tiny_areas_as_dots() {
+        let tiny = [
+            (10.0, 12.0),
+            (10.0, 12.001),
+            (10.001, 12.001),
+            (10.001, 12.0),
+        ];
+        let objs = objects(
+            square(1, 10.0, 10.0).into_iter().chain(
+                tiny.iter()
+                    .zip(5..)
+                    .map(|(&(lat, lon), id)| OsmObj::Node(node(id, lat, lon)))
+                    .chain([
+                        way(10, &[1, 2, 3, 4, 1], &[]),
+                        way(11, &[5, 6, 7, 8, 5], &[]),
+                    ]),
+            ),
+        );
+        let svg = draw(&objs, &["--collapse-small", "1000"], &[], &[10, 11]);
+        assert!(element(&svg, "10").starts_with("<path"));
+        let dot = element(&svg, "11");
+        assert!(dot.starts_with("<use") && attribute(dot, "href") == Some("#node-marker"));
+        // The dot is at the middle of the tiny square.
+        let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf"]);
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, None);
+        let (x, _) = renderer.project(10.0005, 12.0005);
+        let dot_x = attribute(dot, "x").unwrap().parse::<f64>().unwrap();
+        assert!((dot_x - x).abs() < 0.01);
+        assert!(element(&svg, "node-marker").starts_with("<symbol"));
+        let svg = draw(&objs, &[], &[], &[10, 11]);
+        assert!(element(&svg, "11").starts_with("<path"));
+    }
+
+    #[test]
+    fn bundled_fonts_are_embedded_and_set_on_text() {
+        let dir = temp_path("fonts");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Test Sans.otf");
+        std::fs::write(&path, [1, 2, 3]).unwrap();
+        let style = font_style(&path).unwrap().to_string();
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(style.starts_with("<style>"));
+        assert!(style.contains(
+            r#"@font-face { font-family: "Test Sans"; src: url(data:font/otf;base64,AQID) format("opentype"); }"#
+        ));
+        assert!(style.contains(r#"text { font-family: "Test Sans"; }"#));
+        assert!(font_style(std::path::Path::new("/nonexistent/font.ttf")).is_err());
+    }
+
+    #[test]
+    fn depth_fade_fades_nested_relations() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            way(11, &[2, 3], &[]),
+            way(12, &[3, 4], &[]),
+            relation(
+                100,
+                &[
+                    (OsmId::Way(WayId(10)), ""),
+                    (OsmId::Relation(RelationId(101)), ""),
+                ],
+                &[],
+            ),
+            relation(
+                101,
+                &[
+                    (OsmId::Way(WayId(11)), ""),
+                    (OsmId::Relation(RelationId(102)), ""),
+                ],
+                &[],
+            ),
+            relation(102, &[(OsmId::Way(WayId(12)), "")], &[]),
+        ]));
+        let svg = draw(&objs, &["--depth-fade"], &[100], &[]);
+        let opacity =
+            |id| attribute(element(&svg, id), "opacity").map(|o| o.parse::<f64>().unwrap());
+        assert_eq!(opacity("100"), None);
+        // Opacity multiplies down nested groups, so the grandchild is faded twice over.
+        let child = opacity("101").unwrap();
+        let grandchild = child * opacity("102").unwrap();
+        assert!(0.0 < grandchild && grandchild < child && child < 1.0);
+        let svg = draw(&objs, &[], &[100], &[]);
+        assert!(!svg.contains("opacity"));
+    }
+
+    #[test]
+    fn filter_files_select_what_their_rules_match() {
+        let filter = temp_path("filter.txt");
+        let rules =
+            "# Routes and main roads\nrelation type=route\n\nway highway=primary\nnode x=y\nway\n";
+        std::fs::write(&filter, rules).unwrap();
+        let (way_filters, relation_filters) = read_filter(&filter, LogFormat::Text).unwrap();
+        std::fs::remove_file(filter).unwrap();
+        // The malformed rules on lines 5 and 6 are warned about and skipped.
+        assert_eq!((way_filters.len(), relation_filters.len()), (1, 1));
+        let objs = square(1, 10.0, 10.0)
+            .into_iter()
+            .chain([
+                way(10, &[1, 2], &[("highway", "primary")]),
+                way(11, &[2, 3], &[("highway", "secondary")]),
+                way(12, &[3, 4], &[]),
+                relation(100, &[(OsmId::Way(WayId(12)), "")], &[("type", "route")]),
+                relation(
+                    101,
+                    &[(OsmId::Way(WayId(12)), "")],
+                    &[("type", "multipolygon")],
+                ),
+            ])
+            .map(|o| (o, None))
+            .collect::<Vec<_>>();
+        let path = write_pbf("filter", &objs);
+        let mut args = Args::parse_from(["osm-rels-svg", "-d", path.to_str().unwrap()]);
+        args.way_tag.extend(way_filters);
+        args.rel_tag.extend(relation_filters);
+        let selection = select(&mut args, BTreeSet::new(), BTreeSet::new()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(selection.relations, [RelationId(100)].into());
+        assert_eq!(selection.ways, [WayId(10)].into());
+    }
+
+    #[test]
+    fn curvature_colors_bends_warmer_than_straights() {
+        let line = (0..4).map(|i| OsmObj::Node(node(i + 1, 10.0, 10.0 + i as f64)));
+        // Straight for its first two segments, then zig-zagging.
+        let bendy = (0..6).map(|i| {
+            let lat = 12.0 + if i < 3 { 0.0 } else { (i % 2) as f64 };
+            OsmObj::Node(node(i + 5, lat, 10.0 + i as f64))
+        });
+        let objs = objects(line.chain(bendy).chain([
+            way(10, &[1, 2, 3, 4], &[]),
+            way(11, &[5, 6, 7, 8, 9, 10], &[]),
+        ]));
+        let svg = draw(&objs, &["--curvature-color"], &[], &[10, 11]);
+        let colors = |id| {
+            let start = svg.find(element(&svg, id)).unwrap();
+            let group = &svg[start..start + svg[start..].find("</g>").unwrap()];
+            group
+                .split("<path")
+                .skip(1)
+                .map(|p| parse_color(attribute(p, "stroke").unwrap()).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let (straight, curvy) = CURVATURE_COLORS;
+        assert_eq!(colors("10"), [straight]);
+        let bends = colors("11");
+        assert!(bends.len() > 1 && bends[0] == straight);
+        let zigzag = &bends[bends.len() - 1];
+        assert!(zigzag.0 > straight.0 && zigzag.2 < straight.2 && zigzag.0 <= curvy.0);
+    }
+
+    /// The SVGs `--per-value` or `--split-by-tag` write for the given ways, by file name.
+    fn save_values(
+        objs: &BTreeMap<OsmId, OsmObj>,
+        flag: &str,
+        ways: &[i64],
+    ) -> BTreeMap<String, String> {
+        let dir = temp_path(flag);
+        let flags = [
+            "osm-rels-svg",
+            "-d",
+            "x.pbf",
+            flag,
+            "route",
+            dir.to_str().unwrap(),
+        ];
+        let args = Args::parse_from(flags);
+        let ways = ways.iter().map(|&w| WayId(w)).collect();
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        save_by_value(
+            &args,
+            objs,
+            &changes,
+            &edit_times,
+            &BTreeSet::new(),
+            &ways,
+            None,
+        )
+        .unwrap();
+        let files = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, std::fs::read_to_string(path).unwrap())
+            })
+            .collect();
+        std::fs::remove_dir_all(dir).unwrap();
+        files
+    }
+
+    #[test]
+    fn per_value_drawings_share_the_whole_view_box() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[("route", "bus")]),
+            way(11, &[3, 4], &[("route", "tram")]),
+            way(12, &[2, 3], &[]),
+        ]));
+        let files = save_values(&objs, "--per-value", &[10, 11, 12]);
+        let names = files.keys().map(String::as_str).collect::<Vec<_>>();
+        // Features without the tag have no value to go in.
+        assert_eq!(names, ["layer-bus.svg", "layer-tram.svg"]);
+        let (bus, tram) = (&files["layer-bus.svg"], &files["layer-tram.svg"]);
+        assert!(bus.contains(r#"id="10""#) && !bus.contains(r#"id="11""#));
+        assert!(tram.contains(r#"id="11""#) && !tram.contains(r#"id="10""#));
+        assert!(!bus.contains(r#"id="12""#) && !tram.contains(r#"id="12""#));
+        let whole = view_box(&draw(&objs, &[], &[], &[10, 11, 12]));
+        assert_eq!(view_box(bus), whole);
+        assert_eq!(view_box(tram), whole);
+    }
+
+    #[test]
+    fn order_by_area_draws_small_areas_over_large_ones() {
+        let small = [(10.2, 10.2), (10.2, 10.4), (10.4, 10.4), (10.4, 10.2)];
+        let objs = objects(
+            square(1, 10.0, 10.0).into_iter().chain(
+                small
+                    .iter()
+                    .zip(5..)
+                    .map(|(&(lat, lon), id)| OsmObj::Node(node(id, lat, lon)))
+                    .chain([
+                        way(10, &[5, 6, 7, 8, 5], &[("building", "yes")]),
+                        way(11, &[1, 2, 3, 4, 1], &[("landuse", "forest")]),
+                    ]),
+            ),
+        );
+        let small_first = |flags: &[&str]| {
+            let svg = draw(&objs, flags, &[], &[10, 11]);
+            svg.find(r#"id="10""#).unwrap() < svg.find(r#"id="11""#).unwrap()
+        };
+        assert!(small_first(&["--fill"]));
+        assert!(!small_first(&["--fill", "--order-by-area"]));
+    }
+
+    #[test]
+    fn icons_stand_for_what_nodes_are() {
+        let mut parking = node(5, 10.5, 10.5);
+        parking.tags.insert("amenity".into(), "parking".into());
+        let members = [(OsmId::Node(NodeId(5)), ""), (OsmId::Node(NodeId(1)), "")];
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            OsmObj::Node(parking),
+            relation(100, &members, &[("type", "route")]),
+        ]));
+        let svg = draw(&objs, &["--node-markers", "--icons"], &[100], &[]);
+        let uses = svg.matches("<use").count();
+        assert_eq!(uses, 2);
+        assert!(
+            svg.contains(r##"href="#icon-parking""##) && svg.contains(r##"href="#node-marker""##)
+        );
+        assert!(element(&svg, "icon-parking").starts_with("<symbol"));
+        let svg = draw(&objs, &["--node-markers"], &[100], &[]);
+        assert_eq!(svg.matches(r##"href="#node-marker""##).count(), 2);
+        assert!(!svg.contains("icon-parking"));
+    }
+
+    #[test]
+    fn validating_geometry_reports_each_problem_by_way() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2, 3], &[("building", "yes")]),
+            way(11, &[1, 2, 2, 3], &[]),
+            way(12, &[1, 2, 3, 4, 1], &[("building", "yes")]),
+            // Way 13 is only checked as a member of the relation.
+            way(13, &[4], &[]),
+            relation(100, &[(OsmId::Way(WayId(13)), "")], &[]),
+        ]));
+        let ways = [10, 11, 12].map(WayId).into();
+        let report = validate_geometry(&objs, &[RelationId(100)].into(), &ways);
+        assert_eq!(
+            report,
+            "way/10\tis tagged as an area but not closed\n\
+             way/11\trepeats node 2\n\
+             way/13\thas fewer than 2 nodes\n"
+        );
+        assert_eq!(
+            validate_geometry(&objs, &BTreeSet::new(), &BTreeSet::new()),
+            ""
+        );
+    }
+
+    #[test]
+    fn densified_segments_curve_as_projected() {
+        let objs = objects([
+            OsmObj::Node(node(1, 10.0, 10.0)),
+            OsmObj::Node(node(2, 60.0, 40.0)),
+            way(10, &[1, 2], &[]),
+        ]);
+        let points = |flags: &[&str]| {
+            let svg = draw(&objs, flags, &[], &[10]);
+            attribute(element(&svg, "10"), "d")
+                .unwrap()
+                .split(' ')
+                .map(|p| {
+                    let (x, y) = p[1..].split_once(',').unwrap();
+                    (x.parse::<f64>().unwrap(), y.parse::<f64>().unwrap())
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(points(&[]).len(), 2);
+        let dense = points(&["--densify", "1"]);
+        assert_eq!(dense.len(), 51);
+        // Mercator stretches latitudes more the further north, so the line bows off the chord.
+        let (start, end, middle) = (dense[0], dense[50], dense[25]);
+        let chord_y = start.1 + (end.1 - start.1) * (middle.0 - start.0) / (end.0 - start.0);
+        assert!((middle.1 - chord_y).abs() > 100.0);
+        // Where lat/lon are drawn as they are, the added points stay on the line.
+        let flat = points(&["--densify", "1", "--projection", "equirectangular"]);
+        let (start, end, middle) = (flat[0], flat[50], flat[25]);
+        let chord_y = start.1 + (end.1 - start.1) * (middle.0 - start.0) / (end.0 - start.0);
+        assert!((middle.1 - chord_y).abs() < 0.1);
+    }
+
+    #[test]
+    fn merge_group_holds_every_selected_relation() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            way(11, &[3, 4], &[]),
+            way(12, &[2, 3], &[]),
+            relation(100, &[(OsmId::Way(WayId(10)), "")], &[("type", "route")]),
+            relation(101, &[(OsmId::Way(WayId(11)), "")], &[("type", "route")]),
+        ]));
+        let svg = draw(&objs, &["--merge-group", "routes"], &[100, 101], &[12]);
+        let start = svg.find(element(&svg, "routes")).unwrap();
+        let end = start + svg[start..].rfind("</g>").unwrap();
+        let merged = &svg[start..end];
+        assert!(merged.contains(r#"<g id="100""#) && merged.contains(r#"<g id="101""#));
+        // Ways drawn by themselves stay outside it.
+        assert!(svg.find(r#"id="12""#).unwrap() > end);
+        let svg = draw(&objs, &[], &[100, 101], &[12]);
+        assert!(!svg.contains("routes"));
+        let flags = [
+            "osm-rels-svg",
+            "-d",
+            "x.pbf",
+            "--merge-group",
+            "g",
+            "--layers-by",
+            "route",
+        ];
+        assert!(Args::try_parse_from(flags).is_err());
+    }
+
+    #[test]
+    fn excluded_members_are_left_out_of_their_relation() {
+        let members = [(OsmId::Way(WayId(10)), ""), (OsmId::Way(WayId(11)), "")];
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[("highway", "primary")]),
+            way(11, &[2, 3], &[("highway", "construction")]),
+            way(12, &[3, 4], &[("highway", "construction")]),
+            relation(100, &members, &[("type", "route")]),
+        ]));
+        let flags = ["--exclude-tag", "highway=construction"];
+        let svg = draw(&objs, &flags, &[100], &[12]);
+        assert!(svg.contains(r#"id="100""#) && svg.contains(r#"id="10""#));
+        assert!(!svg.contains(r#"id="11""#) && !svg.contains(r#"id="12""#));
+        let svg = draw(&objs, &[], &[100], &[12]);
+        assert!(svg.contains(r#"id="11""#) && svg.contains(r#"id="12""#));
+    }
+
+    #[test]
+    fn world_files_place_the_pixels_of_the_drawing() {
+        let objs = objects(
+            square(1, 10.0, 10.0)
+                .into_iter()
+                .chain([way(10, &[1, 2, 3], &[])]),
+        );
+        let world_file = |flags: &[&str], ways: &[WayId]| {
+            let path = temp_path("world-file.pgw");
+            let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf"].iter().chain(flags));
+            let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+            let mut renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, None);
+            let svg = renderer.render(&BTreeSet::new(), &ways.iter().copied().collect());
+            let result = save_world_file(&path, &args, &svg).map(|()| {
+                let contents = std::fs::read_to_string(&path).unwrap();
+                std::fs::remove_file(&path).unwrap();
+                contents
+                    .lines()
+                    .map(|l| l.parse::<f64>().unwrap())
+                    .collect::<Vec<_>>()
+            });
+            (result.map_err(|e| e.to_string()), super::view_box(&svg))
+        };
+        let k = MapProjection::WebMercator.world_units();
+        // At 192 dpi a PNG has two pixels to each unit of the view box.
+        let (lines, view_box) = world_file(&["-o", "map.png", "--dpi", "192"], &[WayId(10)]);
+        let (lines, view_box) = (lines.unwrap(), view_box.unwrap());
+        assert_eq!(lines[..4], [0.5 * k, 0.0, 0.0, -0.5 * k]);
+        assert!((lines[4] - (view_box[0] + 0.25) * k).abs() < 1e-6);
+        assert!((lines[5] + (view_box[1] + 0.25) * k).abs() < 1e-6);
+        // An SVG's pixels are units of its view box.
+        let (lines, _) = world_file(&["-o", "map.svg"], &[WayId(10)]);
+        assert_eq!(lines.unwrap()[..4], [k, 0.0, 0.0, -k]);
+        let (error, _) = world_file(&["-o", "map.svg"], &[]);
+        assert_eq!(
+            error.unwrap_err(),
+            "--world-file needs something to be drawn"
+        );
+    }
+
+    #[test]
+    fn heatmaps_build_up_where_ways_overlap() {
+        // Ways 10 and 11 lie on top of each other, below way 12 on its own.
+        let objs = objects([
+            OsmObj::Node(node(1, 10.0, 10.0)),
+            OsmObj::Node(node(2, 10.0, 10.001)),
+            OsmObj::Node(node(3, 10.0005, 10.0)),
+            OsmObj::Node(node(4, 10.0005, 10.001)),
+            way(10, &[1, 2], &[("colour", "#ff0000")]),
+            way(11, &[1, 2], &[]),
+            way(12, &[3, 4], &[]),
+        ]);
+        let args = Args::parse_from(["osm-rels-svg", "-d", "x.pbf", "--heatmap"]);
+        let (changes, edit_times) = (BTreeMap::new(), BTreeMap::new());
+        let mut renderer = Renderer::new(&objs, &objs, &changes, &edit_times, &args, None);
+        let svg = renderer.render(&BTreeSet::new(), &[10, 11, 12].map(WayId).into());
+        // Everything is drawn alike, whatever its tags.
+        assert!(!svg.to_string().contains("#ff0000"));
+        let image = tiny_skia::Pixmap::decode_png(&png(&svg, 96.0 * 4.0).unwrap()).unwrap();
+        // The most opaque pixel down the middle column, in the upper or lower half.
+        let (width, height) = (image.width(), image.height());
+        let opacity = |rows: Range<u32>| {
+            rows.map(|y| image.pixel(width / 2, y).unwrap().alpha())
+                .max()
+                .unwrap()
+        };
+        let (single, overlapping) = (opacity(0..height / 2), opacity(height / 2..height));
+        assert!(single > 0 && overlapping > single);
+        assert!((single as f64 - 255.0 * HEATMAP_OPACITY).abs() < 2.0);
+    }
+
+    #[test]
+    fn ids_come_from_the_tag_given() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[("ref", "A1")]),
+            way(11, &[2, 3], &[("ref", "A1")]),
+            way(12, &[3, 4], &[("ref", "7 West")]),
+            way(13, &[4, 1], &[]),
+        ]));
+        let svg = draw(&objs, &["--id-from", "ref"], &[], &[10, 11, 12, 13]);
+        let ids = svg
+            .lines()
+            .filter(|l| l.starts_with("<path"))
+            .map(|l| attribute(l, "id").unwrap())
+            .collect::<Vec<_>>();
+        // Taken ids get the OSM id, and those without the tag keep it.
+        assert_eq!(ids, ["A1", "A1-11", "_7_West", "13"]);
+    }
+
+    #[test]
+    fn link_osm_wraps_features_in_links_to_them() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            way(11, &[3, 4], &[]),
+            relation(100, &[(OsmId::Way(WayId(10)), "")], &[("type", "route")]),
+        ]));
+        let svg = draw(&objs, &["--link-osm"], &[100], &[11]);
+        let link = |id| {
+            let at = svg.find(element(&svg, id)).unwrap();
+            let anchor = &svg[svg[..at].rfind("<a ").unwrap()..at];
+            attribute(anchor.lines().next().unwrap(), "href").unwrap()
+        };
+        assert_eq!(link("10"), "https://www.openstreetmap.org/way/10");
+        assert_eq!(link("11"), "https://www.openstreetmap.org/way/11");
+        assert_eq!(link("100"), "https://www.openstreetmap.org/relation/100");
+        assert!(!draw(&objs, &[], &[100], &[11]).contains("<a "));
+    }
+
+    #[test]
+    fn render_order_puts_ways_or_relations_first() {
+        let members = [
+            (OsmId::Relation(RelationId(101)), ""),
+            (OsmId::Way(WayId(10)), ""),
+            (OsmId::Relation(RelationId(102)), ""),
+            (OsmId::Way(WayId(11)), ""),
+        ];
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            way(11, &[2, 3], &[]),
+            way(12, &[3, 4], &[]),
+            way(13, &[4, 1], &[]),
+            relation(100, &members, &[]),
+            relation(101, &[(OsmId::Way(WayId(12)), "")], &[]),
+            relation(102, &[(OsmId::Way(WayId(13)), "")], &[]),
+        ]));
+        let order = |flags: &[&str]| {
+            let svg = draw(&objs, flags, &[100], &[]);
+            svg.lines()
+                .filter(|l| l.starts_with("<path"))
+                .map(|l| attribute(l, "id").unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(&[]), ["12", "10", "13", "11"]);
+        assert_eq!(
+            order(&["--render-order", "ways-first"]),
+            ["10", "11", "12", "13"]
+        );
+        assert_eq!(
+            order(&["--render-order", "relations-first"]),
+            ["12", "13", "10", "11"]
+        );
+    }
+
+    #[test]
+    fn flow_animate_marches_the_dashes_of_routes() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            way(10, &[1, 2], &[]),
+            way(11, &[3, 4], &[]),
+            relation(100, &[(OsmId::Way(WayId(10)), "")], &[("type", "route")]),
+            relation(101, &[(OsmId::Way(WayId(11)), "")], &[("type", "boundary")]),
+        ]));
+        let svg = draw(&objs, &["--flow-animate"], &[100, 101], &[]);
+        let route = element(&svg, "100");
+        let dashes = format!("{FLOW_DASH} {FLOW_GAP}");
+        assert_eq!(attribute(route, "stroke-dasharray"), Some(dashes.as_str()));
+        // The animation is inside the route's group, along with its member's path.
+        let group = &svg[svg.find(route).unwrap()..svg.find(element(&svg, "101")).unwrap()];
+        let animate = group.lines().find(|l| l.starts_with("<animate")).unwrap();
+        assert_eq!(
+            attribute(animate, "attributeName"),
+            Some("stroke-dashoffset")
+        );
+        assert_eq!(attribute(animate, "repeatCount"), Some("indefinite"));
+        assert!(group.contains(r#"id="10""#));
+        assert_eq!(svg.matches("<animate").count(), 1);
+        assert!(!draw(&objs, &[], &[100, 101], &[]).contains("<animate"));
+    }
+
+    #[test]
+    fn ids_listed_as_the_wrong_kind_are_pointed_out() {
+        let objs = square(1, 10.0, 10.0)
+            .into_iter()
+            .chain([
+                way(10, &[1, 2], &[]),
+                way(11, &[3, 4], &[]),
+                relation(100, &[(OsmId::Way(WayId(11)), "")], &[("type", "route")]),
+            ])
+            .map(|o| (o, None))
+            .collect::<Vec<_>>();
+        // Relation 100 listed as a way, along with a way that is one.
+        let selection = select_from("mistyped", &objs, &[], &[10, 100], &[]).unwrap();
+        assert_eq!(selection.ways, [WayId(10)].into());
+        assert!(selection.relations.is_empty());
+        let found = OsmId::Relation(RelationId(100));
+        assert_eq!(
+            mistyped_message(found, "way"),
+            "way 100 not found: 100 is a relation, not a way"
+        );
+        // With only the mistyped id listed, nothing is left to draw.
+        let selection = select_from("mistyped-only", &objs, &[], &[100], &[]);
+        assert!(matches!(selection, Err(RenderError::EmptySelection)));
+    }
+
+    #[test]
+    fn split_by_tag_frames_each_value_on_its_own() {
+        let objs = objects(square(1, 10.0, 10.0).into_iter().chain([
+            OsmObj::Node(node(5, 14.0, 14.0)),
+            way(10, &[1, 2], &[("route", "A")]),
+            way(11, &[3, 5], &[("route", "B")]),
+            way(12, &[2, 3], &[]),
+        ]));
+        let files = save_values(&objs, "--split-by-tag", &[10, 11, 12]);
+        let names = files.keys().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(names, ["layer-A.svg", "layer-B.svg", "untagged.svg"]);
+        for (name, way) in [
+            ("layer-A.svg", 10),
+            ("layer-B.svg", 11),
+            ("untagged.svg", 12),
+        ] {
+            let alone = draw(&objs, &[], &[], &[way]);
+            assert_eq!(view_box(&files[name]), view_box(&alone), "{name}");
+        }
+        assert_ne!(
+            view_box(&files["layer-A.svg"]),
+            view_box(&files["layer-B.svg"])
+        );
+    }
 }